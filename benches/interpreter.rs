@@ -0,0 +1,87 @@
+// Compare the decoded-instruction cache against exec's nested match
+// Run with `cargo bench`; every ROM in the crate root is run with the cache off and on, first
+// flat out through cycle() and then frame by frame through run_frame() with VIP timing
+
+use chip8::CHIP8;
+use chip8::timing::TimingModel;
+use std::fs;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// Frames to run per ROM, at a rate far above VIP speed so decoding dominates
+const FRAMES: u32 = 2000;
+const INSTRUCTIONS_PER_FRAME: u32 = 1000;
+
+// Run a ROM for FRAMES frames and return how long it took
+fn run(rom_path: &String, use_decode_cache: bool) -> Duration {
+    let mut chip8: CHIP8 = CHIP8::new();
+    CHIP8::load_rom(&mut chip8, rom_path, None, None).expect("could not load ROM");
+    chip8.timing = TimingModel::InstructionsPerFrame(INSTRUCTIONS_PER_FRAME);
+    chip8.use_decode_cache = use_decode_cache;
+
+    let start: Instant = Instant::now();
+    for _ in 0..FRAMES {
+        // Idle-loop detection would cut most frames short; run every instruction instead
+        for _ in 0..INSTRUCTIONS_PER_FRAME {
+            chip8.cycle();
+        }
+        chip8.tick_timers();
+    }
+    black_box(&chip8.video);
+    start.elapsed()
+}
+
+// Run a ROM for FRAMES frames the way the frontends do, and return how long it took
+// VIP timing looks every instruction up again for its cost, and frames stop early once the
+// program is idle
+fn run_frames(rom_path: &String, use_decode_cache: bool) -> Duration {
+    let mut chip8: CHIP8 = CHIP8::new();
+    CHIP8::load_rom(&mut chip8, rom_path, None, None).expect("could not load ROM");
+    chip8.timing = TimingModel::Vip;
+    chip8.use_decode_cache = use_decode_cache;
+
+    let start: Instant = Instant::now();
+    for _ in 0..FRAMES {
+        chip8.run_frame();
+    }
+    black_box(&chip8.video);
+    start.elapsed()
+}
+
+fn main() {
+    let mut roms: Vec<String> = fs::read_dir(".")
+        .expect("could not list ROMs")
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path().to_string_lossy().into_owned())
+        .filter(|path| path.ends_with(".ch8"))
+        .collect();
+    roms.sort();
+
+    let instructions: f64 = (FRAMES * INSTRUCTIONS_PER_FRAME) as f64;
+    for rom_path in &roms {
+        let uncached: Duration = run(rom_path, false);
+        let cached: Duration = run(rom_path, true);
+
+        println!(
+            "{:<60} nested match {:>7.2} ns/op   cached {:>7.2} ns/op   {:.2}x",
+            rom_path,
+            uncached.as_nanos() as f64 / instructions,
+            cached.as_nanos() as f64 / instructions,
+            uncached.as_secs_f64() / cached.as_secs_f64()
+        );
+    }
+
+    println!();
+    for rom_path in &roms {
+        let uncached: Duration = run_frames(rom_path, false);
+        let cached: Duration = run_frames(rom_path, true);
+
+        println!(
+            "{:<60} VIP frames   {:>7.2} us/frame cached {:>7.2} us/frame {:.2}x",
+            rom_path,
+            uncached.as_nanos() as f64 / FRAMES as f64 / 1000.0,
+            cached.as_nanos() as f64 / FRAMES as f64 / 1000.0,
+            uncached.as_secs_f64() / cached.as_secs_f64()
+        );
+    }
+}
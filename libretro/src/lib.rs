@@ -0,0 +1,443 @@
+mod ffi;
+
+use chip8::CHIP8;
+use chip8::keymap;
+use chip8::platform::Platform;
+use chip8::stream::{self, Beeper};
+use ffi::*;
+use std::cell::{Cell, RefCell};
+use std::ffi::{CStr, CString, c_char, c_uint, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+// LIBRETRO CORE
+// The chip as a libretro core, so it runs in RetroArch and other libretro frontends with their
+// save states, shaders and input mapping. The frontend calls retro_run once per 60 Hz frame;
+// every call runs one frame, shows the display and sends one frame of the beeper's audio.
+// Frontends call a core from a single thread, so its state is kept in thread locals.
+
+const LIBRARY_NAME: &CStr = c"chip8";
+const LIBRARY_VERSION: &CStr =
+    match CStr::from_bytes_with_nul(concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes()) {
+        Ok(version) => version,
+        Err(_) => panic!("invalid package version"),
+    };
+// CHIP-8X programs can't be detected from their contents, so they need their own extension
+const VALID_EXTENSIONS: &CStr = c"ch8|c8|c8x|ch8x";
+
+// RetroPad button for every CHIP-8 key
+// The D-pad covers the 2/4/6/8 arrows of the hex keypad and A its centre key, 5
+const JOYPAD_MAP: [(c_uint, usize, &CStr); 16] = [
+    (RETRO_DEVICE_ID_JOYPAD_UP, 0x2, c"Key 2 (up)"),
+    (RETRO_DEVICE_ID_JOYPAD_DOWN, 0x8, c"Key 8 (down)"),
+    (RETRO_DEVICE_ID_JOYPAD_LEFT, 0x4, c"Key 4 (left)"),
+    (RETRO_DEVICE_ID_JOYPAD_RIGHT, 0x6, c"Key 6 (right)"),
+    (RETRO_DEVICE_ID_JOYPAD_A, 0x5, c"Key 5"),
+    (RETRO_DEVICE_ID_JOYPAD_B, 0x0, c"Key 0"),
+    (RETRO_DEVICE_ID_JOYPAD_Y, 0x7, c"Key 7"),
+    (RETRO_DEVICE_ID_JOYPAD_X, 0x9, c"Key 9"),
+    (RETRO_DEVICE_ID_JOYPAD_L, 0x1, c"Key 1"),
+    (RETRO_DEVICE_ID_JOYPAD_R, 0x3, c"Key 3"),
+    (RETRO_DEVICE_ID_JOYPAD_L2, 0xA, c"Key A"),
+    (RETRO_DEVICE_ID_JOYPAD_R2, 0xB, c"Key B"),
+    (RETRO_DEVICE_ID_JOYPAD_SELECT, 0xC, c"Key C"),
+    (RETRO_DEVICE_ID_JOYPAD_START, 0xF, c"Key F"),
+    (RETRO_DEVICE_ID_JOYPAD_L3, 0xD, c"Key D"),
+    (RETRO_DEVICE_ID_JOYPAD_R3, 0xE, c"Key E"),
+];
+
+#[derive(Clone, Copy, Default)]
+struct Callbacks {
+    environment: Option<RetroEnvironment>,
+    video_refresh: Option<RetroVideoRefresh>,
+    audio_sample_batch: Option<RetroAudioSampleBatch>,
+    input_poll: Option<RetroInputPoll>,
+    input_state: Option<RetroInputState>,
+    log: Option<RetroLogPrintf>,
+}
+
+struct Core {
+    chip8: CHIP8,
+    initial_state: Vec<u8>, // State right after loading, for resets
+    buffer: Vec<u32>,       // Colour image of the display, in the XRGB8888 the frontend takes
+    beeper: Beeper,
+}
+
+thread_local! {
+    static CALLBACKS: Cell<Callbacks> = Cell::new(Callbacks::default());
+    static CORE: RefCell<Option<Core>> = const { RefCell::new(None) };
+}
+
+// Update the callbacks the frontend has given
+fn set_callback(update: impl FnOnce(&mut Callbacks)) {
+    CALLBACKS.with(|callbacks| {
+        let mut updated: Callbacks = callbacks.get();
+        update(&mut updated);
+        callbacks.set(updated);
+    });
+}
+
+// Send a message to the frontend's log, or to stderr when it doesn't keep one
+fn log(level: c_uint, message: &str) {
+    match CALLBACKS.get().log {
+        Some(log) => {
+            let message: CString = CString::new(format!("{}\n", message)).unwrap_or_default();
+            unsafe { log(level, c"%s".as_ptr(), message.as_ptr()) };
+        }
+        None => eprintln!("chip8: {}", message),
+    }
+}
+
+// Put the chip back the way it was right after loading
+// Returns false if the chip can't take its initial state any more
+fn reset(core: &mut Core) -> bool {
+    let initial_state: Vec<u8> = core.initial_state.clone();
+    core.chip8.load_state(&initial_state).is_ok()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_environment(callback: RetroEnvironment) {
+    let mut log_callback: RetroLogCallback = RetroLogCallback { log: None };
+    let has_log: bool = unsafe {
+        callback(
+            RETRO_ENVIRONMENT_GET_LOG_INTERFACE,
+            &mut log_callback as *mut RetroLogCallback as *mut c_void,
+        )
+    };
+    set_callback(|callbacks| {
+        callbacks.environment = Some(callback);
+        callbacks.log = if has_log { log_callback.log } else { None };
+    });
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_video_refresh(callback: RetroVideoRefresh) {
+    set_callback(|callbacks| callbacks.video_refresh = Some(callback));
+}
+
+// Audio goes out a frame at a time through the batch callback
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_audio_sample(_callback: RetroAudioSample) {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_audio_sample_batch(callback: RetroAudioSampleBatch) {
+    set_callback(|callbacks| callbacks.audio_sample_batch = Some(callback));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_input_poll(callback: RetroInputPoll) {
+    set_callback(|callbacks| callbacks.input_poll = Some(callback));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_input_state(callback: RetroInputState) {
+    set_callback(|callbacks| callbacks.input_state = Some(callback));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_init() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_deinit() {
+    CORE.with_borrow_mut(|core| *core = None);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+/// # Safety
+/// `info` must point to a writable `retro_system_info`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    if info.is_null() {
+        return;
+    }
+    // ROMs are a few kilobytes at most, so the frontend hands over their contents
+    unsafe {
+        info.write(RetroSystemInfo {
+            library_name: LIBRARY_NAME.as_ptr(),
+            library_version: LIBRARY_VERSION.as_ptr(),
+            valid_extensions: VALID_EXTENSIONS.as_ptr(),
+            need_fullpath: false,
+            block_extract: false,
+        });
+    }
+}
+
+/// # Safety
+/// `info` must point to a writable `retro_system_av_info`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    if info.is_null() {
+        return;
+    }
+    let platform: Platform = CORE.with_borrow(|core| {
+        core.as_ref()
+            .map_or(Platform::Chip8, |core| core.chip8.platform)
+    });
+    let (width, height): (usize, usize) = (platform.width(), platform.height());
+    // The hi-res display is the tallest one
+    let max_height: usize = Platform::HiRes.height();
+
+    unsafe {
+        info.write(RetroSystemAvInfo {
+            geometry: RetroGameGeometry {
+                base_width: width as c_uint,
+                base_height: height as c_uint,
+                max_width: width as c_uint,
+                max_height: max_height as c_uint,
+                aspect_ratio: width as f32 / height as f32,
+            },
+            timing: RetroSystemTiming {
+                fps: 60.0,
+                sample_rate: stream::SAMPLE_RATE as f64,
+            },
+        });
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_reset() {
+    CORE.with_borrow_mut(|core| {
+        if let Some(core) = core.as_mut() {
+            reset(core);
+        }
+    });
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_run() {
+    let callbacks: Callbacks = CALLBACKS.get();
+    if let Some(input_poll) = callbacks.input_poll {
+        unsafe { input_poll() };
+    }
+
+    CORE.with_borrow_mut(|loaded| {
+        let Some(core) = loaded.as_mut() else {
+            return;
+        };
+
+        // A CHIP-8 key is down while its button or keyboard key is
+        if let Some(input_state) = callbacks.input_state {
+            let mut keypad: [bool; 16] = [false; 16];
+            for (button, key, _) in JOYPAD_MAP {
+                keypad[key] |= unsafe { input_state(0, RETRO_DEVICE_JOYPAD, 0, button) } != 0;
+            }
+            // libretro key codes for letters and digits are their lowercase ASCII codes
+            for (key, host_key) in keymap::KEYPAD.iter().enumerate() {
+                keypad[key] |=
+                    unsafe { input_state(0, RETRO_DEVICE_KEYBOARD, 0, *host_key as c_uint) } != 0;
+            }
+            core.chip8.keypad = keypad;
+        }
+
+        // A panic can't unwind into the frontend, which would abort it. It shouldn't happen at
+        // all, but if it does the state the chip was left in can't be trusted, so the game
+        // starts over.
+        if panic::catch_unwind(AssertUnwindSafe(|| core.chip8.run_frame())).is_err() {
+            if reset(core) {
+                log(RETRO_LOG_ERROR, "internal error, the game was reset");
+            } else {
+                log(RETRO_LOG_ERROR, "internal error, the game was stopped");
+                *loaded = None;
+            }
+            return;
+        }
+
+        if let Some(video_refresh) = callbacks.video_refresh {
+            let width: usize = core.chip8.platform.width();
+            let height: usize = core.chip8.platform.height();
+            core.chip8.render(&mut core.buffer);
+            core.chip8.display_dirty = false;
+            unsafe {
+                video_refresh(
+                    core.buffer.as_ptr() as *const c_void,
+                    width as c_uint,
+                    height as c_uint,
+                    width * 4,
+                )
+            };
+        }
+
+        // The beeper is mono, and libretro takes interleaved stereo
+        if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+            let samples: Vec<i16> = core
+                .beeper
+                .frame(core.chip8.sound_timer > 0)
+                .chunks_exact(2)
+                .flat_map(|sample| {
+                    let sample: i16 = i16::from_le_bytes([sample[0], sample[1]]);
+                    [sample, sample]
+                })
+                .collect();
+            unsafe { audio_sample_batch(samples.as_ptr(), samples.len() / 2) };
+        }
+    });
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_serialize_size() -> usize {
+    CORE.with_borrow(|core| core.as_ref().map_or(0, |core| core.chip8.state_size()))
+}
+
+/// # Safety
+/// `data` must point to `size` writable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+    CORE.with_borrow_mut(|core| {
+        let Some(core) = core.as_mut() else {
+            return false;
+        };
+        let state: Vec<u8> = core.chip8.save_state();
+        if state.len() > size {
+            return false;
+        }
+        unsafe { ptr::copy_nonoverlapping(state.as_ptr(), data as *mut u8, state.len()) };
+        true
+    })
+}
+
+/// # Safety
+/// `data` must point to `size` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+    let state: &[u8] = unsafe { slice::from_raw_parts(data as *const u8, size) };
+    CORE.with_borrow_mut(|core| {
+        core.as_mut()
+            .is_some_and(|core| core.chip8.load_state(state).is_ok())
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+/// # Safety
+/// `game` must be null or point to a valid `retro_game_info` whose data is `size` bytes long.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_load_game(game: *const RetroGameInfo) -> bool {
+    let Some(game) = (unsafe { game.as_ref() }) else {
+        return false;
+    };
+    if game.data.is_null() {
+        return false;
+    }
+    let rom: &[u8] = unsafe { slice::from_raw_parts(game.data as *const u8, game.size) };
+    let platform: Option<Platform> = (!game.path.is_null())
+        .then(|| unsafe { CStr::from_ptr(game.path) }.to_string_lossy())
+        .and_then(|path| Platform::from_file_name(&path));
+
+    let callbacks: Callbacks = CALLBACKS.get();
+    let Some(environment) = callbacks.environment else {
+        return false;
+    };
+    let mut pixel_format: c_uint = RETRO_PIXEL_FORMAT_XRGB8888;
+    if !unsafe {
+        environment(
+            RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+            &mut pixel_format as *mut c_uint as *mut c_void,
+        )
+    } {
+        log(RETRO_LOG_ERROR, "the frontend doesn't support XRGB8888");
+        return false;
+    }
+    // The list ends with an entry without a description
+    let mut descriptors: Vec<RetroInputDescriptor> = JOYPAD_MAP
+        .iter()
+        .map(|(button, _, description)| RetroInputDescriptor {
+            port: 0,
+            device: RETRO_DEVICE_JOYPAD,
+            index: 0,
+            id: *button,
+            description: description.as_ptr(),
+        })
+        .collect();
+    descriptors.push(RetroInputDescriptor {
+        port: 0,
+        device: 0,
+        index: 0,
+        id: 0,
+        description: ptr::null(),
+    });
+    unsafe {
+        environment(
+            RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+            descriptors.as_mut_ptr() as *mut c_void,
+        )
+    };
+
+    let mut chip8: CHIP8 = CHIP8::new();
+    // Cheats and achievements write to memory through retro_get_memory_data, which the decode
+    // cache wouldn't notice
+    chip8.use_decode_cache = false;
+    if let Err(error) = CHIP8::load_rom_bytes(&mut chip8, rom, platform, None) {
+        log(RETRO_LOG_ERROR, &error.to_string());
+        return false;
+    }
+    let initial_state: Vec<u8> = chip8.save_state();
+    let buffer: Vec<u32> = vec![0; chip8.platform.width() * chip8.platform.height()];
+    CORE.with_borrow_mut(|core| {
+        *core = Some(Core {
+            chip8,
+            initial_state,
+            buffer,
+            beeper: Beeper::default(),
+        })
+    });
+    true
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const RetroGameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_unload_game() {
+    CORE.with_borrow_mut(|core| *core = None);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+// The chip's memory, for cheats and achievements
+// Loading states and resetting copy into it, so it stays in place until the game is unloaded
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    if id != RETRO_MEMORY_SYSTEM_RAM {
+        return ptr::null_mut();
+    }
+    CORE.with_borrow_mut(|core| {
+        core.as_mut().map_or(ptr::null_mut(), |core| {
+            core.chip8.memory.as_mut_ptr() as *mut c_void
+        })
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    if id != RETRO_MEMORY_SYSTEM_RAM {
+        return 0;
+    }
+    CORE.with_borrow(|core| core.as_ref().map_or(0, |core| core.chip8.memory.len()))
+}
@@ -0,0 +1,496 @@
+// AHEAD-OF-TIME RECOMPILER
+// Finds the code reachable from a ROM's entry point and writes it out as a Rust module, with one
+// function per basic block working directly on the library's CHIP8 state.
+//
+// Straight-line instructions become Rust statements; the instruction that ends a block (a jump,
+// call, skip, draw, memory write or anything else that can change the flow or end the frame) is
+// left to the interpreter with CHIP8::cycle(), so timing, idle detection and stalls are unchanged.
+// Code the analysis can't see (computed Bnnn jumps) has no block and is interpreted, and every
+// block checks its own bytes first so self-modified code is interpreted too.
+//
+// The module's install() hooks the blocks into a chip with the same ROM loaded.
+
+use chip8::CHIP8;
+use chip8::memory::MemoryMap;
+use chip8::platform::Platform;
+use chip8::timing;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fmt::Write;
+use std::process;
+
+const USAGE: &str = "usage: chip8-recompile [--platform chip8|hires|chip8x] \
+[--memory chip8|vip2k|eti660|xochip|LOAD:SIZE:FONT] <rom> > blocks.rs";
+
+// How an instruction fits into a basic block
+enum Kind {
+    // Only touches V0-VF and I, so it is written out as Rust
+    Inline,
+    // Doesn't touch the PC, memory or the frame state, so it is run with CHIP8::exec()
+    Exec,
+    // Ends the block; the successors are the addresses the PC can go to next
+    End(Vec<usize>),
+}
+
+// A run of instructions entered at the first one and left after the last one
+struct Block {
+    start: usize,
+    opcodes: Vec<u16>,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut rom_path: Option<String> = None;
+    let mut platform: Option<Platform> = None;
+    let mut memory_map: Option<MemoryMap> = None;
+
+    let mut arg_iter = args[1..].iter();
+    while let Some(arg) = arg_iter.next() {
+        let result: Result<(), String> = match arg.as_str() {
+            "--platform" => arg_iter
+                .next()
+                .ok_or(String::from("--platform needs a value"))
+                .and_then(|name| name.parse().map(|parsed| platform = Some(parsed))),
+            "--memory" => arg_iter
+                .next()
+                .ok_or(String::from("--memory needs a value"))
+                .and_then(|spec| spec.parse().map(|parsed| memory_map = Some(parsed))),
+            _ if arg.starts_with("--") => Err(format!("unknown option '{}'", arg)),
+            _ => {
+                rom_path = Some(arg.clone());
+                Ok(())
+            }
+        };
+        if let Err(error) = result {
+            eprintln!("{}\n{}", error, USAGE);
+            process::exit(2);
+        }
+    }
+    let Some(rom_path) = rom_path else {
+        eprintln!("no ROM file given\n{}", USAGE);
+        process::exit(2);
+    };
+
+    // Load the ROM the same way the emulator does, so the addresses and entry point match
+    let mut chip8: CHIP8 = CHIP8::new();
+    if let Err(error) = CHIP8::load_rom(&mut chip8, &rom_path, platform, memory_map) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+
+    let (blocks, computed_jumps): (Vec<Block>, BTreeSet<usize>) = find_blocks(&chip8);
+    print!("{}", emit(&chip8, &rom_path, &blocks, &computed_jumps));
+}
+
+// Read the opcode at an address, if all of it is in memory
+fn opcode_at(memory: &[u8], address: usize) -> Option<u16> {
+    if address + 1 < memory.len() {
+        Some(u16::from_be_bytes([memory[address], memory[address + 1]]))
+    } else {
+        None
+    }
+}
+
+// Sort an instruction by how it can be recompiled, following CHIP8::decode()
+// Returns None for opcodes the interpreter ignores, which are taken to be data
+fn classify(opcode: u16, address: usize, platform: Platform) -> Option<Kind> {
+    let next: usize = address + 2;
+    let nnn: usize = (opcode & 0x0FFF) as usize;
+    let chip8x: bool = platform == Platform::Chip8X;
+
+    let kind: Kind = match (opcode & 0xF000) >> 12 {
+        0x0 => match opcode {
+            0x00E0 => Kind::End(vec![next]),
+            // Returns go back to the instruction after a call, which is found from the call
+            0x00EE => Kind::End(vec![]),
+            0x0230 if platform == Platform::HiRes => Kind::End(vec![next]),
+            0x02A0 if chip8x => Kind::End(vec![next]),
+            _ => return None,
+        },
+        0x1 => Kind::End(vec![nnn]),
+        0x2 => Kind::End(vec![nnn, next]),
+        0x3 | 0x4 | 0x9 => Kind::End(vec![next, next + 2]),
+        0x5 => match opcode & 0x000F {
+            0x0 => Kind::End(vec![next, next + 2]),
+            0x1 if chip8x => Kind::Exec,
+            _ => return None,
+        },
+        0x6 | 0x7 | 0xA => Kind::Inline,
+        0x8 => match opcode & 0x000F {
+            0x0..=0x5 | 0x7 => Kind::Inline,
+            _ => return None,
+        },
+        // CHIP-8X colours, or a jump that can only be followed at run time
+        0xB if chip8x => Kind::End(vec![next]),
+        0xB => Kind::End(vec![]),
+        0xC => Kind::Exec,
+        0xD => Kind::End(vec![next]),
+        0xE => match opcode & 0x00FF {
+            0x9E | 0xA1 => Kind::End(vec![next, next + 2]),
+            0xF2 | 0xF5 if chip8x => Kind::End(vec![next, next + 2]),
+            _ => return None,
+        },
+        0xF => match opcode & 0x00FF {
+            0x1E => Kind::Inline,
+            0x07 | 0x15 | 0x18 | 0x29 | 0x30 | 0x65 => Kind::Exec,
+            0x0A | 0x33 | 0x55 => Kind::End(vec![next]),
+            0xF8 | 0xFB if chip8x => Kind::End(vec![next]),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(kind)
+}
+
+// Follow every path from the entry point to find the reachable instructions, then cut them into
+// basic blocks at every address something jumps, calls, skips or returns to
+// Also returns the addresses of the computed jumps, which the analysis can't follow
+fn find_blocks(chip8: &CHIP8) -> (Vec<Block>, BTreeSet<usize>) {
+    let memory: &[u8] = &chip8.memory;
+    let entry: usize = chip8.PC as usize;
+
+    let mut reachable: BTreeSet<usize> = BTreeSet::new();
+    let mut leaders: BTreeSet<usize> = BTreeSet::from([entry]);
+    let mut computed_jumps: BTreeSet<usize> = BTreeSet::new();
+    let mut work: Vec<usize> = vec![entry];
+
+    while let Some(address) = work.pop() {
+        if !reachable.insert(address) {
+            continue;
+        }
+        let Some(opcode) = opcode_at(memory, address) else {
+            continue;
+        };
+        let successors: Vec<usize> = match classify(opcode, address, chip8.platform) {
+            Some(Kind::End(successors)) => {
+                if successors.is_empty() && (opcode & 0xF000) == 0xB000 {
+                    computed_jumps.insert(address);
+                }
+                leaders.extend(successors.iter().copied());
+                successors
+            }
+            Some(_) => vec![address + 2],
+            None => vec![],
+        };
+        work.extend(successors);
+    }
+
+    let mut blocks: Vec<Block> = Vec::new();
+    for start in leaders.iter().copied() {
+        let mut opcodes: Vec<u16> = Vec::new();
+        let mut address: usize = start;
+        while let Some(opcode) = opcode_at(memory, address) {
+            let Some(kind) = classify(opcode, address, chip8.platform) else {
+                break;
+            };
+            opcodes.push(opcode);
+            address += 2;
+            // Blocks also end where another one starts, so every entry into the code has a block
+            if matches!(kind, Kind::End(_)) || leaders.contains(&address) {
+                break;
+            }
+        }
+        if !opcodes.is_empty() {
+            blocks.push(Block { start, opcodes });
+        }
+    }
+
+    (blocks, computed_jumps)
+}
+
+// Write out the Rust module
+fn emit(
+    chip8: &CHIP8,
+    rom_path: &str,
+    blocks: &[Block],
+    computed_jumps: &BTreeSet<usize>,
+) -> String {
+    let mut out: String = String::new();
+
+    writeln!(out, "// Recompiled from {} by chip8-recompile", rom_path).unwrap();
+    writeln!(
+        out,
+        "// {} basic blocks, entered at {:#05x}",
+        blocks.len(),
+        chip8.PC
+    )
+    .unwrap();
+    for address in computed_jumps {
+        writeln!(
+            out,
+            "// Computed jump at {:#05x}; its targets are interpreted",
+            address
+        )
+        .unwrap();
+    }
+    out.push_str(
+        "
+use chip8::CHIP8;
+use chip8::platform::Platform;
+
+// Run the recompiled blocks on this chip
+pub fn install(chip8: &mut CHIP8) {
+    chip8.recompiled = Some(run_block);
+}
+
+// Run the block at the PC, if there is one
+pub fn run_block(chip8: &mut CHIP8, max_instructions: u32, cycle_budget: i64) -> Option<(u32, i64)> {
+",
+    );
+    // The blocks index memory directly, so they need the layout they were recompiled for
+    writeln!(
+        out,
+        "    if chip8.platform != Platform::{:?} || chip8.memory.len() != {:#x} {{\n        return None;\n    }}",
+        chip8.platform,
+        chip8.memory.len()
+    )
+    .unwrap();
+    out.push_str("    match chip8.PC {\n");
+    for block in blocks {
+        writeln!(
+            out,
+            "        {:#05x} => block_{:03x}(chip8, max_instructions, cycle_budget),",
+            block.start, block.start
+        )
+        .unwrap();
+    }
+    out.push_str("        _ => None,\n    }\n}\n");
+
+    // The blocks leading to each block are listed with it, to make the module easier to read
+    let mut callers: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for block in blocks {
+        let last: usize = block.start + 2 * (block.opcodes.len() - 1);
+        let opcode: u16 = block.opcodes[block.opcodes.len() - 1];
+        let successors: Vec<usize> = match classify(opcode, last, chip8.platform) {
+            Some(Kind::End(successors)) => successors,
+            _ => vec![last + 2],
+        };
+        for successor in successors {
+            callers.entry(successor).or_default().push(block.start);
+        }
+    }
+
+    for block in blocks {
+        emit_block(&mut out, chip8, block, callers.get(&block.start));
+    }
+
+    out
+}
+
+fn emit_block(out: &mut String, chip8: &CHIP8, block: &Block, callers: Option<&Vec<usize>>) {
+    let end: usize = block.start + 2 * block.opcodes.len();
+    let bytes: &[u8] = &chip8.memory[block.start..end];
+    let (body, last): (&[u16], u16) = (
+        &block.opcodes[..block.opcodes.len() - 1],
+        block.opcodes[block.opcodes.len() - 1],
+    );
+    let last_address: usize = end - 2;
+    // None of the instructions before the last one depend on the machine state for their cost
+    let body_cycles: u32 = body
+        .iter()
+        .map(|opcode| timing::vip_cycles(*opcode, 0, 0))
+        .sum();
+
+    writeln!(out).unwrap();
+    match callers {
+        Some(callers) => {
+            let callers: Vec<String> = callers
+                .iter()
+                .map(|caller| format!("{:#05x}", caller))
+                .collect();
+            writeln!(out, "// From {}", callers.join(", ")).unwrap();
+        }
+        None => writeln!(out, "// Entry point").unwrap(),
+    }
+    writeln!(
+        out,
+        "fn block_{:03x}(chip8: &mut CHIP8, max_instructions: u32, cycle_budget: i64) -> Option<(u32, i64)> {{",
+        block.start
+    )
+    .unwrap();
+
+    // The interpreter would only start the last instruction with some of the budget left
+    writeln!(
+        out,
+        "    if max_instructions < {} || cycle_budget <= {} {{\n        return None;\n    }}",
+        block.opcodes.len(),
+        body_cycles
+    )
+    .unwrap();
+    let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:#04x}", byte)).collect();
+    writeln!(
+        out,
+        "    // Self-modified code is left to the interpreter\n    if chip8.memory[{:#05x}..{:#05x}] != [{}] {{\n        return None;\n    }}",
+        block.start,
+        end,
+        bytes.join(", ")
+    )
+    .unwrap();
+    writeln!(out).unwrap();
+
+    for (i, opcode) in body.iter().enumerate() {
+        let address: usize = block.start + 2 * i;
+        writeln!(
+            out,
+            "    // {:#05x}: {:04X}  {}",
+            address,
+            opcode,
+            mnemonic(*opcode, chip8.platform)
+        )
+        .unwrap();
+        let lines: Vec<String> = statement(*opcode, chip8.platform);
+        if lines.len() == 1 {
+            writeln!(out, "    {}", lines[0]).unwrap();
+        } else {
+            // A scope of its own keeps the temporaries apart
+            writeln!(out, "    {{").unwrap();
+            for line in lines {
+                writeln!(out, "        {}", line).unwrap();
+            }
+            writeln!(out, "    }}").unwrap();
+        }
+    }
+
+    writeln!(
+        out,
+        "    // {:#05x}: {:04X}  {}",
+        last_address,
+        last,
+        mnemonic(last, chip8.platform)
+    )
+    .unwrap();
+    writeln!(out, "    chip8.PC = {:#05x};", last_address).unwrap();
+    if body_cycles > 0 {
+        writeln!(
+            out,
+            "    let cycles: i64 = {} + chip8.instruction_cycles() as i64;",
+            body_cycles
+        )
+        .unwrap();
+    } else {
+        writeln!(
+            out,
+            "    let cycles: i64 = chip8.instruction_cycles() as i64;"
+        )
+        .unwrap();
+    }
+    writeln!(out, "    chip8.cycle();").unwrap();
+    writeln!(out, "    Some(({}, cycles))", block.opcodes.len()).unwrap();
+    writeln!(out, "}}").unwrap();
+}
+
+// Rust lines for an instruction inside a block, matching the interpreter's handler
+fn statement(opcode: u16, platform: Platform) -> Vec<String> {
+    let x: u16 = (opcode & 0x0F00) >> 8;
+    let y: u16 = (opcode & 0x00F0) >> 4;
+    let kk: u16 = opcode & 0x00FF;
+    let vx: String = format!("chip8.registers[{:#x}]", x);
+    let vy: String = format!("chip8.registers[{:#x}]", y);
+
+    if let Some(Kind::Exec) = classify(opcode, 0, platform) {
+        return vec![format!("chip8.exec({:#06x});", opcode)];
+    }
+
+    match (opcode & 0xF000) >> 12 {
+        0x6 => vec![format!("{} = {:#04x};", vx, kk)],
+        0x7 => vec![format!("{} = {}.wrapping_add({:#04x});", vx, vx, kk)],
+        0x8 => match opcode & 0x000F {
+            0x0 => vec![format!("{} = {};", vx, vy)],
+            0x1 => vec![format!("{} |= {};", vx, vy)],
+            0x2 => vec![format!("{} &= {};", vx, vy)],
+            0x3 => vec![format!("{} ^= {};", vx, vy)],
+            // VF is only touched when there is a carry
+            0x4 => vec![
+                format!(
+                    "let (sum, carry): (u8, bool) = {}.overflowing_add({});",
+                    vx, vy
+                ),
+                format!("{} = sum;", vx),
+                String::from("if carry {"),
+                String::from("    chip8.registers[0xf] = 1;"),
+                String::from("}"),
+            ],
+            0x5 => vec![
+                format!("let (vx, vy): (u8, u8) = ({}, {});", vx, vy),
+                format!("{} = vx.wrapping_sub(vy);", vx),
+                String::from("chip8.registers[0xf] = (vx > vy) as u8;"),
+            ],
+            _ => vec![
+                format!("let (vx, vy): (u8, u8) = ({}, {});", vx, vy),
+                format!("{} = vy.wrapping_sub(vx);", vx),
+                String::from("chip8.registers[0xf] = (vy > vx) as u8;"),
+            ],
+        },
+        0xA => vec![format!("chip8.IR = {:#05x};", opcode & 0x0FFF)],
+        _ => vec![format!("chip8.IR = chip8.IR.wrapping_add({} as u16);", vx)],
+    }
+}
+
+// Assembly language for an instruction, in the usual CHIP-8 notation
+fn mnemonic(opcode: u16, platform: Platform) -> String {
+    let x: u16 = (opcode & 0x0F00) >> 8;
+    let y: u16 = (opcode & 0x00F0) >> 4;
+    let n: u16 = opcode & 0x000F;
+    let kk: u16 = opcode & 0x00FF;
+    let nnn: u16 = opcode & 0x0FFF;
+    let chip8x: bool = platform == Platform::Chip8X;
+
+    match (opcode & 0xF000) >> 12 {
+        0x0 => match opcode {
+            0x00E0 => String::from("CLS"),
+            0x00EE => String::from("RET"),
+            0x0230 if platform == Platform::HiRes => String::from("CLS (hi-res)"),
+            0x02A0 if chip8x => String::from("BGCOL"),
+            _ => format!("SYS {:#05x}", nnn),
+        },
+        0x1 => format!("JP {:#05x}", nnn),
+        0x2 => format!("CALL {:#05x}", nnn),
+        0x3 => format!("SE V{:X}, {:#04x}", x, kk),
+        0x4 => format!("SNE V{:X}, {:#04x}", x, kk),
+        0x5 if n == 1 && chip8x => format!("ADD V{:X}, V{:X} (BCD)", x, y),
+        0x5 => format!("SE V{:X}, V{:X}", x, y),
+        0x6 => format!("LD V{:X}, {:#04x}", x, kk),
+        0x7 => format!("ADD V{:X}, {:#04x}", x, kk),
+        0x8 => match n {
+            0x0 => format!("LD V{:X}, V{:X}", x, y),
+            0x1 => format!("OR V{:X}, V{:X}", x, y),
+            0x2 => format!("AND V{:X}, V{:X}", x, y),
+            0x3 => format!("XOR V{:X}, V{:X}", x, y),
+            0x4 => format!("ADD V{:X}, V{:X}", x, y),
+            0x5 => format!("SUB V{:X}, V{:X}", x, y),
+            0x6 => format!("SHR V{:X}, V{:X}", x, y),
+            0x7 => format!("SUBN V{:X}, V{:X}", x, y),
+            0xE => format!("SHL V{:X}, V{:X}", x, y),
+            _ => format!("DW {:#06x}", opcode),
+        },
+        0x9 => format!("SNE V{:X}, V{:X}", x, y),
+        0xA => format!("LD I, {:#05x}", nnn),
+        0xB if chip8x && n == 0 => format!("COL V{:X}, V{:X}", x, y),
+        0xB if chip8x => format!("COL V{:X}, V{:X}, {}", x, y, n),
+        0xB => format!("JP V0, {:#05x}", nnn),
+        0xC => format!("RND V{:X}, {:#04x}", x, kk),
+        0xD => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        0xE => match kk {
+            0x9E => format!("SKP V{:X}", x),
+            0xA1 => format!("SKNP V{:X}", x),
+            0xF2 if chip8x => format!("SKP2 V{:X}", x),
+            0xF5 if chip8x => format!("SKNP2 V{:X}", x),
+            _ => format!("DW {:#06x}", opcode),
+        },
+        _ => match kk {
+            0x07 => format!("LD V{:X}, DT", x),
+            0x0A => format!("LD V{:X}, K", x),
+            0x15 => format!("LD DT, V{:X}", x),
+            0x18 => format!("LD ST, V{:X}", x),
+            0x1E => format!("ADD I, V{:X}", x),
+            0x29 => format!("LD F, V{:X}", x),
+            0x30 => format!("LD HF, V{:X}", x),
+            0x33 => format!("LD B, V{:X}", x),
+            0x55 => format!("LD [I], V{:X}", x),
+            0x65 => format!("LD V{:X}, [I]", x),
+            0xF8 if chip8x => format!("OUT V{:X}", x),
+            0xFB if chip8x => format!("IN V{:X}", x),
+            _ => format!("DW {:#06x}", opcode),
+        },
+    }
+}
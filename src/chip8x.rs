@@ -0,0 +1,36 @@
+// CHIP-8X SPECIFICS
+// CHIP-8X runs on a COSMAC VIP fitted with the VP-590 colour board and VP-580 second keypad
+
+// Colours the VP-590 can produce, indexed by the 3-bit colour number
+pub const COLORS: [u32; 8] = [
+    0x000000, // 0 - Black
+    0xFF0000, // 1 - Red
+    0x0000FF, // 2 - Blue
+    0xFF00FF, // 3 - Violet
+    0x00FF00, // 4 - Green
+    0xFFFF00, // 5 - Yellow
+    0x00FFFF, // 6 - Aqua
+    0xFFFFFF, // 7 - White
+];
+
+// Background colours in the order 02A0 cycles through them (blue, black, green, red)
+pub const BACKGROUNDS: [u32; 4] = [0x0000FF, 0x000000, 0x00FF00, 0xFF0000];
+
+// Foreground colour of every zone after reset (red)
+pub const DEFAULT_FOREGROUND: u8 = 1;
+
+// Foreground colours are set for 8 pixel wide zones
+pub const ZONE_WIDTH: usize = 8;
+// Bxy0 works on zones 4 pixels high, Bxyn works on single rows
+pub const ZONE_HEIGHT: usize = 4;
+
+// Host side of the FxF8 / FxFB I/O port opcodes
+// On hardware the port drives the VP-595 sound board, or whatever else is plugged in
+pub trait IoPort {
+    // FxF8 - Output Vx to the I/O port
+    fn output(&mut self, value: u8);
+
+    // FxFB - Wait for input from the I/O port
+    // Returning None keeps the interpreter waiting on the same instruction
+    fn input(&mut self) -> Option<u8>;
+}
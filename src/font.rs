@@ -0,0 +1,162 @@
+use crate::{FONTSET, FONTSET_SIZE};
+use std::fs;
+
+// Every large character takes 10 bytes (8 by 10 pixels)
+pub const LARGE_CHAR_SIZE: usize = 10;
+// Memory reserved for a font: 16 small characters followed by up to 16 large ones
+pub const FONT_MEMORY_SIZE: usize = FONTSET_SIZE as usize + 16 * LARGE_CHAR_SIZE;
+
+// Original COSMAC VIP interpreter font
+const VIP_FONT: [u8; FONTSET_SIZE as usize] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+// DREAM 6800 font (3 pixels wide)
+const DREAM_6800_FONT: [u8; FONTSET_SIZE as usize] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+// ETI-660 font (3 pixels wide)
+const ETI_660_FONT: [u8; FONTSET_SIZE as usize] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+// SUPER-CHIP large digits used by Fx30
+const SCHIP_LARGE_FONT: [u8; 10 * LARGE_CHAR_SIZE] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+];
+
+// A set of character sprites for Fx29 and Fx30
+// The large characters are stored in memory right after the small ones
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Font {
+    pub small: [u8; FONTSET_SIZE as usize], // 16 characters of 5 bytes (4 by 5 pixels)
+    pub large: Vec<u8>,                     // Large characters of 10 bytes, possibly none
+}
+
+impl Font {
+    // Look up a built-in font by name
+    pub fn builtin(name: &str) -> Option<Font> {
+        let (small, large): ([u8; FONTSET_SIZE as usize], &[u8]) =
+            match name.to_ascii_lowercase().as_str() {
+                "chip8" => (FONTSET, &[]),
+                "vip" => (VIP_FONT, &[]),
+                "dream6800" | "dream-6800" => (DREAM_6800_FONT, &[]),
+                "eti660" | "eti-660" => (ETI_660_FONT, &[]),
+                "schip" | "superchip" => (FONTSET, &SCHIP_LARGE_FONT),
+                _ => return None,
+            };
+
+        Some(Font {
+            small,
+            large: large.to_vec(),
+        })
+    }
+
+    // Load a font from a file holding the 80 bytes of small characters, optionally followed
+    // by 10 bytes for each large character
+    pub fn from_file(filename_path: &str) -> Result<Font, String> {
+        let bytes: Vec<u8> = fs::read(filename_path)
+            .map_err(|error| format!("could not read font '{}': {}", filename_path, error))?;
+
+        let small_size: usize = FONTSET_SIZE as usize;
+        let large_size: usize = bytes.len().saturating_sub(small_size);
+        if bytes.len() < small_size
+            || bytes.len() > FONT_MEMORY_SIZE
+            || !large_size.is_multiple_of(LARGE_CHAR_SIZE)
+        {
+            return Err(format!(
+                "font '{}' is {} bytes; expected {} bytes plus up to 16 large characters of {} bytes",
+                filename_path,
+                bytes.len(),
+                small_size,
+                LARGE_CHAR_SIZE
+            ));
+        }
+
+        let mut small: [u8; FONTSET_SIZE as usize] = [0; FONTSET_SIZE as usize];
+        small.copy_from_slice(&bytes[..small_size]);
+
+        Ok(Font {
+            small,
+            large: bytes[small_size..].to_vec(),
+        })
+    }
+
+    // Look up a built-in font by name, or load it from a file otherwise
+    pub fn from_name_or_file(name: &str) -> Result<Font, String> {
+        match Font::builtin(name) {
+            Some(font) => Ok(font),
+            None => Font::from_file(name),
+        }
+    }
+
+    // Number of large characters available to Fx30
+    pub fn large_chars(&self) -> usize {
+        self.large.len() / LARGE_CHAR_SIZE
+    }
+}
+
+impl Default for Font {
+    // The font the emulator has always used
+    fn default() -> Font {
+        Font {
+            small: FONTSET,
+            large: Vec::new(),
+        }
+    }
+}
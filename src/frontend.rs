@@ -0,0 +1,228 @@
+use crate::options::Options;
+use crate::pacing::{self, FastForward, FramePacer};
+use chip8::CHIP8;
+use chip8::idle::Idle;
+use chip8::palette;
+use chip8::phosphor::Phosphor;
+use chip8::recording::Recorder;
+use chip8::scale::{self, Image};
+use chip8::screenshot;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Instant;
+
+// Frontend controls, mapped to host keys by every frontend
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    Quit,
+    FastForward,  // Hold to fast-forward
+    Pause,        // Pause or resume
+    FrameAdvance, // Run a single frame while paused
+    Slower,       // Halve the speed, down to 1/8
+    Faster,       // Double the speed, back up to normal
+    Palette,      // Switch to the next built-in palette
+    Screenshot,   // Save the display as a PNG
+    Record,       // Start or stop recording an animation
+}
+
+const MAX_SLOW_MOTION: u32 = 8;
+
+// Where the display is shown and the keys are read from
+pub trait Frontend {
+    // False once the user has closed the frontend
+    fn is_open(&self) -> bool;
+
+    // Set the state of both keypads from the host keys
+    fn read_keypads(&mut self, keypad: &mut [bool; 16], keypad2: &mut [bool; 16]);
+
+    fn is_down(&self, control: Control) -> bool;
+
+    // Whether the control was pressed since the last update, counting key repeats if 'repeat'
+    fn was_pressed(&mut self, control: Control, repeat: bool) -> bool;
+
+    // Show a new image and take in new input
+    fn present(&mut self, pixels: &[u32], width: usize, height: usize);
+
+    // Take in new input while the image stays the same
+    fn update(&mut self);
+
+    fn set_title(&mut self, title: &str);
+
+    // Tell the user something, e.g. where a screenshot was saved
+    fn notify(&mut self, message: &str);
+}
+
+// Run a chip in a frontend until the user quits
+pub fn run(frontend: &mut impl Frontend, mut chip8: CHIP8, options: &Options) {
+    let width: usize = chip8.platform.width();
+    let height: usize = chip8.platform.height();
+
+    let mut pacer: FramePacer = FramePacer::new();
+    let mut paused: bool = false;
+    // Speed is divided by this while in slow motion
+    let mut slow_motion: u32 = 1;
+    let mut title: String = String::from("CHIP8");
+    // Colour image of the display, reused for every frame shown
+    let mut buffer: Vec<u32> = vec![0; width * height];
+    let mut phosphor: Option<Phosphor> = options.persistence.map(Phosphor::new);
+    let mut recorder: Option<Recorder> = None;
+    // Position in the built-in palettes; a palette of the user's own comes before the first one
+    let mut palette_index: usize = palette::PALETTES
+        .iter()
+        .position(|(_, palette)| *palette == chip8.palette)
+        .unwrap_or(palette::PALETTES.len() - 1);
+
+    while frontend.is_open() && !frontend.is_down(Control::Quit) {
+        // Read both keypads
+        frontend.read_keypads(&mut chip8.keypad, &mut chip8.keypad2);
+
+        if frontend.was_pressed(Control::Screenshot, false) {
+            let path: PathBuf = screenshot::timestamped_path(&options.rom_path, "png");
+            // The screenshot is what is on screen, persistence and all
+            match screenshot::save_png(
+                &chip8,
+                &buffer,
+                &options.rom_path,
+                &options.filters,
+                options.screenshot_scale,
+                &path,
+            ) {
+                Ok(()) => frontend.notify(&format!("Saved {}", path.display())),
+                Err(error) => frontend.notify(&error),
+            }
+        }
+
+        if frontend.was_pressed(Control::Record, false) {
+            match recorder.take() {
+                Some(finished) => frontend.notify(&stop_recording(finished, chip8.frame_count)),
+                None => {
+                    let extension: &str = options.record_format.extension();
+                    let path: PathBuf = screenshot::timestamped_path(&options.rom_path, extension);
+                    frontend.notify(&format!("Recording to {}", path.display()));
+                    recorder = Some(Recorder::new(options.record_format, path));
+                    // Start with what is on screen now
+                    chip8.display_dirty = true;
+                }
+            }
+        }
+
+        // Speed controls
+        if frontend.was_pressed(Control::Pause, false) {
+            paused = !paused;
+        }
+        if frontend.was_pressed(Control::Slower, false) {
+            slow_motion = (slow_motion * 2).min(MAX_SLOW_MOTION);
+        }
+        if frontend.was_pressed(Control::Faster, false) {
+            slow_motion = (slow_motion / 2).max(1);
+        }
+        let fast_forward: bool = frontend.is_down(Control::FastForward);
+
+        if frontend.was_pressed(Control::Palette, false) {
+            palette_index = (palette_index + 1) % palette::PALETTES.len();
+            chip8.palette = palette::PALETTES[palette_index].1;
+            chip8.display_dirty = true;
+        }
+
+        // Run every frame that is due, then show the result
+        // The timers tick once per emulated frame, so they keep in step with the game at any speed
+        let mut frames: u32 = 0;
+        let mut sleep: bool = true;
+        if paused {
+            // Time stands still while paused, apart from single frames on request
+            pacer.skip();
+            if frontend.was_pressed(Control::FrameAdvance, true) {
+                chip8.run_frame();
+                frames = 1;
+            }
+        } else if fast_forward && options.fast_forward == FastForward::Uncapped {
+            // Fill one host frame with as many emulated frames as possible
+            let start: Instant = Instant::now();
+            while start.elapsed() < pacing::FRAME_TIME {
+                chip8.run_frame();
+                frames += 1;
+            }
+            pacer.skip();
+            sleep = false;
+        } else {
+            let speed: f64 = match options.fast_forward {
+                FastForward::Times(times) if fast_forward => times,
+                _ => 1.0 / slow_motion as f64,
+            };
+            pacer.set_speed(speed);
+            frames = pacer.frames_due();
+            for _ in 0..frames {
+                chip8.run_frame();
+            }
+        }
+
+        // Show the speed in the title
+        let new_title: String = if paused {
+            String::from("CHIP8 [paused]")
+        } else if chip8.idle != Idle::Running && !fast_forward {
+            format!("CHIP8 [{}]", chip8.idle)
+        } else if fast_forward {
+            String::from("CHIP8 [fast-forward]")
+        } else if slow_motion > 1 {
+            format!("CHIP8 [1/{} speed]", slow_motion)
+        } else {
+            String::from("CHIP8")
+        };
+        if new_title != title {
+            frontend.set_title(&new_title);
+            title = new_title;
+        }
+        // Only redraw when something on screen has changed, or is still fading out
+        let fading: bool = frames > 0 && phosphor.as_ref().is_some_and(Phosphor::fading);
+        if chip8.display_dirty || fading {
+            chip8.display_dirty = false;
+            chip8.render(&mut buffer);
+            if let Some(phosphor) = phosphor.as_mut() {
+                phosphor.apply(&mut buffer, frames);
+            }
+            let filtered: Option<Image> = (!options.filters.is_empty())
+                .then(|| scale::apply(&options.filters, Image::new(width, height, buffer.clone())));
+            let (pixels, image_width, image_height): (&[u32], usize, usize) = match &filtered {
+                Some(image) => (&image.pixels, image.width, image.height),
+                None => (&buffer, width, height),
+            };
+            frontend.present(pixels, image_width, image_height);
+            // Recordings get exactly what is on screen
+            // A recording that can't be written any more is given up
+            if let Some(recording) = recorder.as_mut()
+                && let Err(error) =
+                    recording.capture(pixels, image_width, image_height, chip8.frame_count)
+            {
+                frontend.notify(&error);
+                recorder = None;
+            }
+        } else {
+            // Keep the frontend responsive between frames
+            frontend.update();
+        }
+
+        if sleep {
+            thread::sleep(pacer.time_to_next_frame());
+        }
+    }
+
+    if let Some(finished) = recorder {
+        frontend.notify(&stop_recording(finished, chip8.frame_count));
+    }
+
+    if pacer.dropped_frames() > 0 {
+        eprintln!(
+            "Dropped {} frames the host could not keep up with",
+            pacer.dropped_frames()
+        );
+    }
+}
+
+// Write out a recording that has been stopped, and say how it went
+fn stop_recording(recorder: Recorder, frame_count: u64) -> String {
+    let path: PathBuf = recorder.path().to_path_buf();
+    match recorder.finish(frame_count) {
+        Ok(()) => format!("Saved {}", path.display()),
+        Err(error) => error,
+    }
+}
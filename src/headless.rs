@@ -0,0 +1,74 @@
+use crate::options::Options;
+use chip8::CHIP8;
+use chip8::phosphor::Phosphor;
+use chip8::scale::{self, Image};
+use chip8::stream::{Beeper, VideoWriter};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+// Run a chip for a fixed number of frames without a window, as fast as possible
+// Every frame is written to stdout when a video format was asked for, and the sound timer's tone
+// to the audio file, so the run can be piped into an encoder. The last frame can also be shown as
+// an inline image. No keys are pressed.
+pub fn run(mut chip8: CHIP8, options: &Options, frames: u64) -> Result<(), String> {
+    let width: usize = chip8.platform.width();
+    let height: usize = chip8.platform.height();
+
+    let mut video: Option<VideoWriter<BufWriter<io::Stdout>>> = options
+        .video
+        .map(|format| VideoWriter::new(BufWriter::new(io::stdout()), format));
+    let mut audio: Option<(BufWriter<File>, Beeper)> = match &options.audio {
+        Some(path) => {
+            let file: File = File::create(path)
+                .map_err(|error| format!("could not create '{}': {}", path, error))?;
+            Some((BufWriter::new(file), Beeper::default()))
+        }
+        None => None,
+    };
+    let mut buffer: Vec<u32> = vec![0; width * height];
+    let mut phosphor: Option<Phosphor> = options.persistence.map(Phosphor::new);
+    // Persistence builds up over every frame, even when only the last one is shown
+    let render_every_frame: bool =
+        video.is_some() || (options.inline.is_some() && phosphor.is_some());
+
+    for _ in 0..frames {
+        chip8.run_frame();
+
+        if render_every_frame {
+            chip8.render(&mut buffer);
+            if let Some(phosphor) = phosphor.as_mut() {
+                phosphor.apply(&mut buffer, 1);
+            }
+        }
+
+        if let Some(video) = video.as_mut() {
+            let image: Image =
+                scale::apply(&options.filters, Image::new(width, height, buffer.clone()));
+            video
+                .write_frame(&image)
+                .map_err(|error| format!("could not write video: {}", error))?;
+        }
+
+        if let Some((file, beeper)) = audio.as_mut() {
+            file.write_all(&beeper.frame(chip8.sound_timer > 0))
+                .map_err(|error| format!("could not write audio: {}", error))?;
+        }
+    }
+
+    if let Some((mut file, _)) = audio {
+        file.flush()
+            .map_err(|error| format!("could not write audio: {}", error))?;
+    }
+
+    // Show the last frame like a screenshot, straight in the terminal, as the video would show it
+    if let Some(protocol) = options.inline {
+        if !render_every_frame {
+            chip8.render(&mut buffer);
+        }
+        let image: Image = scale::apply(&options.filters, Image::new(width, height, buffer))
+            .enlarge(options.screenshot_scale);
+        writeln!(io::stdout(), "{}", protocol.encode(&image))
+            .map_err(|error| format!("could not write image: {}", error))?;
+    }
+    Ok(())
+}
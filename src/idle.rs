@@ -0,0 +1,26 @@
+use std::fmt;
+
+// Whether the program is doing useful work, as seen by the idle-loop detection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Idle {
+    // The program is making progress
+    Running,
+    // A 1nnn jumps to itself; nothing but the timers will ever change again
+    Halted,
+    // Fx0A is waiting for a key press
+    WaitingForKey,
+    // A loop went round without changing anything, so it is polling the timers or keypad
+    Spinning,
+}
+
+impl fmt::Display for Idle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name: &str = match self {
+            Idle::Running => "running",
+            Idle::Halted => "halted",
+            Idle::WaitingForKey => "waiting for key",
+            Idle::Spinning => "idle",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,234 @@
+use crate::scale::Image;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+// INLINE IMAGES
+// Escape sequences that make terminals with graphics support show an image in place, for looking
+// at the display without a window, e.g. at the end of a headless run.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    // DEC Sixel, understood by xterm (with -ti vt340), mlterm, foot, WezTerm and others
+    Sixel,
+    // The kitty terminal graphics protocol, also understood by WezTerm, Konsole and Ghostty
+    Kitty,
+}
+
+impl FromStr for Protocol {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Protocol, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "sixel" => Ok(Protocol::Sixel),
+            "kitty" => Ok(Protocol::Kitty),
+            _ => Err(format!("unknown inline image protocol '{}'", name)),
+        }
+    }
+}
+
+impl Protocol {
+    // The escape sequence that shows 'image'
+    pub fn encode(&self, image: &Image) -> String {
+        match self {
+            Protocol::Sixel => sixel(image),
+            Protocol::Kitty => kitty(image),
+        }
+    }
+}
+
+// Sixel colour registers terminals are guaranteed to have
+const SIXEL_COLORS: usize = 256;
+// Base64 bytes sent in every kitty graphics command
+const KITTY_CHUNK: usize = 4096;
+
+// Sixel images are sent in bands 6 pixels high; each band is sent once for every colour in it,
+// as a row of characters whose bits say which of the 6 pixels in a column have that colour
+fn sixel(image: &Image) -> String {
+    // Colours beyond the number of registers are cut down to 3 bits of red and green and 2 of
+    // blue, which only happens with filters that blend colours
+    let mut colors: Vec<u32> = Vec::new();
+    let mut registers: HashMap<u32, usize> = HashMap::new();
+    for pixel in &image.pixels {
+        if !registers.contains_key(pixel) {
+            registers.insert(*pixel, colors.len());
+            colors.push(*pixel);
+        }
+    }
+    let pixels: Vec<u32> = if colors.len() > SIXEL_COLORS {
+        let reduced: Vec<u32> = image.pixels.iter().map(|pixel| pixel & 0xE0E0C0).collect();
+        colors.clear();
+        registers.clear();
+        for pixel in &reduced {
+            if !registers.contains_key(pixel) {
+                registers.insert(*pixel, colors.len());
+                colors.push(*pixel);
+            }
+        }
+        reduced
+    } else {
+        image.pixels.clone()
+    };
+
+    // Start the image with 1:1 pixels, then define the colours in percent
+    let mut output: String = format!("\x1bP0;1q\"1;1;{};{}", image.width, image.height);
+    for (register, color) in colors.iter().enumerate() {
+        let [_, red, green, blue]: [u8; 4] = color.to_be_bytes();
+        let percent = |channel: u8| -> u32 { (channel as u32 * 100 + 127) / 255 };
+        let _ = write!(
+            output,
+            "#{};2;{};{};{}",
+            register,
+            percent(red),
+            percent(green),
+            percent(blue)
+        );
+    }
+
+    for band in (0..image.height).step_by(6) {
+        let rows: usize = (image.height - band).min(6);
+        // The sixel bits of every column, for every colour in the band
+        let mut columns: HashMap<usize, Vec<u8>> = HashMap::new();
+        for x in 0..image.width {
+            for row in 0..rows {
+                let register: usize = registers[&pixels[(band + row) * image.width + x]];
+                columns
+                    .entry(register)
+                    .or_insert_with(|| vec![0; image.width])[x] |= 1 << row;
+            }
+        }
+        let mut used: Vec<(usize, Vec<u8>)> = columns.into_iter().collect();
+        used.sort_by_key(|(register, _)| *register);
+
+        for (i, (register, bits)) in used.iter().enumerate() {
+            // Every colour after the first goes back to the start of the band
+            if i > 0 {
+                output.push('$');
+            }
+            let _ = write!(output, "#{}", register);
+            // Runs of the same character are sent as a count
+            let mut x: usize = 0;
+            while x < bits.len() {
+                let run: usize = bits[x..].iter().take_while(|b| **b == bits[x]).count();
+                let character: char = (b'?' + bits[x]) as char;
+                if run > 3 {
+                    let _ = write!(output, "!{}{}", run, character);
+                } else {
+                    output.extend(std::iter::repeat_n(character, run));
+                }
+                x += run;
+            }
+        }
+        output.push('-');
+    }
+
+    output.push_str("\x1b\\");
+    output
+}
+
+// Kitty images are sent as base64 RGB data, split over commands that each carry part of it
+fn kitty(image: &Image) -> String {
+    let data: String = base64(&image.rgb_bytes());
+    let chunks: Vec<&str> = data
+        .as_bytes()
+        .chunks(KITTY_CHUNK)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or(""))
+        .collect();
+
+    let mut output: String = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more: u8 = (i + 1 < chunks.len()) as u8;
+        if i == 0 {
+            let _ = write!(
+                output,
+                "\x1b_Ga=T,f=24,s={},v={},m={};{}\x1b\\",
+                image.width, image.height, more, chunk
+            );
+        } else {
+            let _ = write!(output, "\x1b_Gm={};{}\x1b\\", more, chunk);
+        }
+    }
+    output
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut output: String = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group: u32 = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(group >> (18 - 6 * i)) as usize & 0x3F] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: u32 = 0x000000;
+    const WHITE: u32 = 0xFFFFFF;
+
+    #[test]
+    fn sixel_sends_each_colour_of_a_band_in_turn() {
+        // A white column and a black one, with a white row under them in a second band
+        let mut pixels: Vec<u32> = [WHITE, BLACK].repeat(6);
+        pixels.extend([WHITE, WHITE]);
+        let image: Image = Image::new(2, 7, pixels);
+        assert_eq!(
+            sixel(&image),
+            "\x1bP0;1q\"1;1;2;7#0;2;100;100;100#1;2;0;0;0#0~?$#1?~-#0@@-\x1b\\"
+        );
+    }
+
+    #[test]
+    fn sixel_sends_runs_of_more_than_3_as_a_count() {
+        let image: Image = Image::new(6, 1, vec![WHITE, WHITE, WHITE, WHITE, BLACK, BLACK]);
+        assert_eq!(
+            sixel(&image),
+            "\x1bP0;1q\"1;1;6;1#0;2;100;100;100#1;2;0;0;0#0!4@??$#1!4?@@-\x1b\\"
+        );
+    }
+
+    #[test]
+    fn sixel_cuts_colours_down_when_there_are_too_many() {
+        // 257 colours are more than there are registers, and they cut down to 4
+        let image: Image = Image::new(257, 1, (0..257).collect());
+        let output: String = sixel(&image);
+        assert!(output.contains("#0;2;0;0;0#1;2;0;0;25#2;2;0;0;50#3;2;0;0;75#0"));
+        assert!(!output.contains("#4;"));
+    }
+
+    #[test]
+    fn kitty_sends_base64_rgb() {
+        let image: Image = Image::new(2, 1, vec![0xFF0000, 0x0000FF]);
+        assert_eq!(kitty(&image), "\x1b_Ga=T,f=24,s=2,v=1,m=0;/wAAAAD/\x1b\\");
+    }
+
+    #[test]
+    fn kitty_splits_long_data_over_commands() {
+        // 1025 pixels are 3075 bytes, 4100 in base64
+        let image: Image = Image::new(1025, 1, vec![BLACK; 1025]);
+        let expected: String = format!(
+            "\x1b_Ga=T,f=24,s=1025,v=1,m=1;{}\x1b\\\x1b_Gm=0;AAAA\x1b\\",
+            "A".repeat(4096)
+        );
+        assert_eq!(kitty(&image), expected);
+    }
+
+    #[test]
+    fn base64_pads_the_last_group() {
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+    }
+}
@@ -0,0 +1,383 @@
+// CRANELIFT JIT
+// Straight runs of register-only instructions (a basic block, cut short at the first instruction
+// that touches anything else) are compiled to native code. Everything else, and any block whose
+// bytes have been written over since it was compiled, is left to the interpreter.
+
+use crate::{CHIP8, timing};
+use cranelift_codegen::Context;
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{AbiParam, InstBuilder, MemFlags, Value, types};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{FuncId, Module, default_libcall_names};
+
+// Blocks shorter than this aren't worth leaving the interpreter for
+const MIN_BLOCK_LENGTH: usize = 2;
+
+// Native code for a block; takes pointers to V0-VF and to I
+type BlockFunction = unsafe extern "C" fn(*mut u8, *mut u16);
+
+// A compiled basic block
+struct Block {
+    code: Vec<u8>,           // Bytes the block was compiled from
+    instructions: u32,       // Number of CHIP-8 instructions in the block
+    cycles: i64,             // VIP machine cycles for the whole block
+    cycles_before_last: i64, // VIP machine cycles for all but the last instruction
+    function: BlockFunction,
+}
+
+// What is known about the code at an address
+enum Entry {
+    // Not looked at yet
+    Unknown,
+    // No block can start here, or the block was written over; always interpret
+    Interpret,
+    Compiled(Block),
+}
+
+pub struct Jit {
+    module: JITModule,
+    context: Context,
+    builder_context: FunctionBuilderContext,
+    entries: Vec<Entry>,            // Indexed by address
+    pub last_error: Option<String>, // Why the last block that failed to compile did, if any did
+}
+
+impl Jit {
+    // Set up Cranelift for the host machine
+    pub fn new() -> Result<Jit, String> {
+        let mut flags = settings::builder();
+        flags
+            .set("opt_level", "speed")
+            .map_err(|error| error.to_string())?;
+        let isa = cranelift_native::builder()
+            .map_err(|error| error.to_string())?
+            .finish(settings::Flags::new(flags))
+            .map_err(|error| error.to_string())?;
+
+        let module: JITModule = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+
+        Ok(Jit {
+            context: module.make_context(),
+            module,
+            builder_context: FunctionBuilderContext::new(),
+            entries: Vec::new(),
+            last_error: None,
+        })
+    }
+
+    // Run the compiled block at the PC, if there is one
+    // The block only runs if all of it fits in 'max_instructions', and if the interpreter would
+    // also have started its last instruction with 'cycle_budget' VIP machine cycles left.
+    // Returns the number of instructions run and the VIP machine cycles they took.
+    pub fn run_block(
+        &mut self,
+        chip8: &mut CHIP8,
+        max_instructions: u32,
+        cycle_budget: i64,
+    ) -> Option<(u32, i64)> {
+        if self.entries.len() != chip8.memory.len() {
+            self.entries = (0..chip8.memory.len()).map(|_| Entry::Unknown).collect();
+        }
+
+        let address: usize = chip8.PC as usize;
+        if address >= self.entries.len() {
+            return None;
+        }
+
+        if let Entry::Unknown = self.entries[address] {
+            self.entries[address] = self.compile(chip8, address);
+        }
+
+        let Entry::Compiled(block) = &self.entries[address] else {
+            return None;
+        };
+
+        // Self-modifying code goes back to the interpreter for good
+        if chip8.memory[address..address + block.code.len()] != block.code[..] {
+            self.entries[address] = Entry::Interpret;
+            return None;
+        }
+
+        if block.instructions > max_instructions || block.cycles_before_last >= cycle_budget {
+            return None;
+        }
+
+        // SAFETY: the block only reads and writes the 16 registers and I it is handed
+        unsafe { (block.function)(chip8.registers.as_mut_ptr(), &mut chip8.IR) };
+        chip8.advance_pc(2 * block.instructions as i32);
+
+        Some((block.instructions, block.cycles))
+    }
+
+    // Find the block starting at 'address' and compile it
+    fn compile(&mut self, chip8: &CHIP8, address: usize) -> Entry {
+        let mut opcodes: Vec<u16> = Vec::new();
+        let mut pc: usize = address;
+        while pc + 1 < chip8.memory.len() {
+            let opcode: u16 = u16::from_be_bytes([chip8.memory[pc], chip8.memory[pc + 1]]);
+            if !compilable(opcode) {
+                break;
+            }
+            opcodes.push(opcode);
+            pc += 2;
+        }
+
+        if opcodes.len() < MIN_BLOCK_LENGTH {
+            return Entry::Interpret;
+        }
+
+        let function: BlockFunction = match self.translate(&opcodes) {
+            Ok(function) => function,
+            // The interpreter runs the block instead, so this is only kept for whoever wants to know
+            Err(error) => {
+                let error: String = format!("could not compile block at {:#x}: {}", address, error);
+                trace!("JIT: {}", error);
+                self.last_error = Some(error);
+                return Entry::Interpret;
+            }
+        };
+
+        // None of these instructions depend on the machine state for their cost
+        let costs: Vec<i64> = opcodes
+            .iter()
+            .map(|opcode| timing::vip_cycles(*opcode, 0, 0) as i64)
+            .collect();
+        let cycles: i64 = costs.iter().sum();
+
+        Entry::Compiled(Block {
+            code: chip8.memory[address..pc].to_vec(),
+            instructions: opcodes.len() as u32,
+            cycles,
+            cycles_before_last: cycles - costs[costs.len() - 1],
+            function,
+        })
+    }
+
+    // Translate a block to Cranelift IR and compile it
+    // Every register is loaded on entry, kept in SSA values and stored back on exit
+    fn translate(&mut self, opcodes: &[u16]) -> Result<BlockFunction, String> {
+        let pointer = self.module.target_config().pointer_type();
+        let params: &mut Vec<AbiParam> = &mut self.context.func.signature.params;
+        params.push(AbiParam::new(pointer));
+        params.push(AbiParam::new(pointer));
+
+        let mut builder = FunctionBuilder::new(&mut self.context.func, &mut self.builder_context);
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        builder.seal_block(entry);
+
+        let registers_pointer: Value = builder.block_params(entry)[0];
+        let ir_pointer: Value = builder.block_params(entry)[1];
+        let flags: MemFlags = MemFlags::trusted();
+
+        let mut v: Vec<Value> = (0..16)
+            .map(|i| builder.ins().load(types::I8, flags, registers_pointer, i))
+            .collect();
+        let mut i: Value = builder.ins().load(types::I16, flags, ir_pointer, 0);
+
+        for opcode in opcodes {
+            let x: usize = ((opcode & 0x0F00) >> 8) as usize;
+            let y: usize = ((opcode & 0x00F0) >> 4) as usize;
+            let kk: i64 = (opcode & 0x00FF) as i64;
+
+            match (opcode & 0xF000) >> 12 {
+                0x6 => v[x] = builder.ins().iconst(types::I8, kk),
+                0x7 => v[x] = builder.ins().iadd_imm(v[x], kk),
+                0x8 => match opcode & 0x000F {
+                    0x0 => v[x] = v[y],
+                    0x1 => v[x] = builder.ins().bor(v[x], v[y]),
+                    0x2 => v[x] = builder.ins().band(v[x], v[y]),
+                    0x3 => v[x] = builder.ins().bxor(v[x], v[y]),
+                    // VF is only touched when there is a carry, like the interpreter does
+                    0x4 => {
+                        let vx: Value = v[x];
+                        let sum: Value = builder.ins().iadd(vx, v[y]);
+                        let carry: Value = builder.ins().icmp(IntCC::UnsignedLessThan, sum, vx);
+                        v[x] = sum;
+                        let one: Value = builder.ins().iconst(types::I8, 1);
+                        v[0xF] = builder.ins().select(carry, one, v[0xF]);
+                    }
+                    0x5 => {
+                        let (vx, vy): (Value, Value) = (v[x], v[y]);
+                        v[x] = builder.ins().isub(vx, vy);
+                        v[0xF] = builder.ins().icmp(IntCC::UnsignedGreaterThan, vx, vy);
+                    }
+                    0x7 => {
+                        let (vx, vy): (Value, Value) = (v[x], v[y]);
+                        v[x] = builder.ins().isub(vy, vx);
+                        v[0xF] = builder.ins().icmp(IntCC::UnsignedGreaterThan, vy, vx);
+                    }
+                    _ => unreachable!("not a compilable opcode: {:#x}", opcode),
+                },
+                0xA => i = builder.ins().iconst(types::I16, (opcode & 0x0FFF) as i64),
+                0xF => {
+                    let vx: Value = builder.ins().uextend(types::I16, v[x]);
+                    i = builder.ins().iadd(i, vx);
+                }
+                _ => unreachable!("not a compilable opcode: {:#x}", opcode),
+            }
+        }
+
+        for (offset, value) in v.iter().enumerate() {
+            builder
+                .ins()
+                .store(flags, *value, registers_pointer, offset as i32);
+        }
+        builder.ins().store(flags, i, ir_pointer, 0);
+        builder.ins().return_(&[]);
+        builder.finalize();
+
+        let defined: Result<FuncId, String> = self
+            .module
+            .declare_anonymous_function(&self.context.func.signature)
+            .map_err(|error| error.to_string())
+            .and_then(|id| {
+                self.module
+                    .define_function(id, &mut self.context)
+                    .map(|_| id)
+                    .map_err(|error| error.to_string())
+            });
+        // The context is reused for the next block, so it is cleared even if compiling failed
+        self.module.clear_context(&mut self.context);
+        let id: FuncId = defined?;
+        self.module
+            .finalize_definitions()
+            .map_err(|error| error.to_string())?;
+
+        // SAFETY: the function was just compiled with the BlockFunction signature
+        Ok(unsafe {
+            std::mem::transmute::<*const u8, BlockFunction>(self.module.get_finalized_function(id))
+        })
+    }
+}
+
+// Instructions that only read and write V0-VF and I, and always fall through to the next one
+fn compilable(opcode: u16) -> bool {
+    match (opcode & 0xF000) >> 12 {
+        0x6 | 0x7 | 0xA => true,
+        0x8 => matches!(opcode & 0x000F, 0x0 | 0x1 | 0x2 | 0x3 | 0x4 | 0x5 | 0x7),
+        0xF => opcode & 0x00FF == 0x1E,
+        _ => false,
+    }
+}
+
+// Describe the first difference between two machines, if there is one
+fn difference(interpreted: &CHIP8, compiled: &CHIP8) -> Option<String> {
+    if interpreted.PC != compiled.PC {
+        return Some(format!("PC {:#x} != {:#x}", interpreted.PC, compiled.PC));
+    }
+    if interpreted.registers != compiled.registers {
+        return Some(format!(
+            "V0-VF {:02x?} != {:02x?}",
+            interpreted.registers, compiled.registers
+        ));
+    }
+    if interpreted.IR != compiled.IR {
+        return Some(format!("I {:#x} != {:#x}", interpreted.IR, compiled.IR));
+    }
+    if interpreted.st_pointer != compiled.st_pointer || interpreted.stack != compiled.stack {
+        return Some(String::from("stack differs"));
+    }
+    if interpreted.delay_timer != compiled.delay_timer
+        || interpreted.sound_timer != compiled.sound_timer
+    {
+        return Some(String::from("timers differ"));
+    }
+    if let Some(address) = (0..interpreted.memory.len())
+        .find(|address| interpreted.memory[*address] != compiled.memory[*address])
+    {
+        return Some(format!("memory differs at {:#x}", address));
+    }
+    if interpreted.video != compiled.video {
+        return Some(String::from("display differs"));
+    }
+    None
+}
+
+// Differential test: run two identically set up machines, one interpreted and one with the JIT,
+// and check that their state matches after every frame
+pub fn differential_test(
+    interpreted: &mut CHIP8,
+    compiled: &mut CHIP8,
+    frames: u32,
+) -> Result<(), String> {
+    if let Some(difference) = difference(interpreted, compiled) {
+        return Err(format!(
+            "machines differ before the first frame: {}",
+            difference
+        ));
+    }
+
+    for frame in 0..frames {
+        interpreted.run_frame();
+        compiled.run_frame();
+
+        if let Some(difference) = difference(interpreted, compiled) {
+            return Err(format!(
+                "state differs after frame {}: {}",
+                frame + 1,
+                difference
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryMap;
+    use std::fs;
+    use std::path::PathBuf;
+
+    // Every ROM bundled in the crate root runs the same with and without the JIT
+    #[test]
+    fn jit_matches_the_interpreter_on_the_bundled_roms() {
+        let root: &str = env!("CARGO_MANIFEST_DIR");
+        let mut roms: Vec<PathBuf> = fs::read_dir(root)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "ch8"))
+            .collect();
+        roms.sort();
+        assert!(!roms.is_empty(), "no ROMs in {}", root);
+
+        for rom in &roms {
+            let rom_bytes: Vec<u8> = fs::read(rom).unwrap();
+            let mut interpreted: CHIP8 = CHIP8::new();
+            let mut compiled: CHIP8 = CHIP8::new();
+            for chip8 in [&mut interpreted, &mut compiled] {
+                chip8.seed(0);
+                CHIP8::load_rom_bytes(chip8, &rom_bytes, None, None).unwrap();
+            }
+            compiled.jit = Some(Box::new(Jit::new().unwrap()));
+
+            if let Err(error) = differential_test(&mut interpreted, &mut compiled, 600) {
+                panic!("{}: {}", rom.display(), error);
+            }
+        }
+    }
+
+    // A block that runs up to the end of memory leaves the PC at the start of memory
+    #[test]
+    fn block_at_the_end_of_memory_wraps_the_pc() {
+        let mut chip8: CHIP8 = CHIP8::new();
+        let memory_map: MemoryMap = "0xFFF0:65536:0x50".parse().unwrap();
+        let rom: Vec<u8> = (0..8u8)
+            .flat_map(|register| [0x60 | register, 0x01])
+            .collect();
+        CHIP8::load_rom_bytes(&mut chip8, &rom, None, Some(memory_map)).unwrap();
+        chip8.jit = Some(Box::new(Jit::new().unwrap()));
+
+        let ran: Option<u32> = chip8
+            .run_block(u32::MAX, i64::MAX)
+            .map(|(instructions, _)| instructions);
+        assert_eq!(ran, Some(8));
+        assert_eq!(chip8.PC, 0x0000);
+        assert_eq!(chip8.registers[..8], [1; 8]);
+    }
+}
@@ -0,0 +1,15 @@
+// HOST KEYBOARD LAYOUT
+// The host keys that stand in for the keypads, shared by every frontend so they all agree. The
+// 4x4 block 1234/QWER/ASDF/ZXCV takes the place of the VIP hex keypad, and the CHIP-8X second
+// keypad is laid out the same way on the numeric keypad. Keys are named by the character on them,
+// and each frontend turns that into its own key codes.
+
+// Keys on the main keyboard for the CHIP-8 keys 0x0 to 0xF
+pub const KEYPAD: [char; 16] = [
+    'x', '1', '2', '3', 'q', 'w', 'e', 'a', 's', 'd', 'z', 'c', '4', 'r', 'f', 'v',
+];
+
+// Keys on the numeric keypad for the second keypad's keys 0x0 to 0xF ('\n' is Enter)
+pub const KEYPAD2: [char; 16] = [
+    '.', '7', '8', '9', '4', '5', '6', '1', '2', '3', '0', '\n', '/', '*', '-', '+',
+];
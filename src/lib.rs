@@ -0,0 +1,1899 @@
+// Print debugging output from the interpreter when built with the "trace" feature
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if cfg!(feature = "trace") {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+pub mod chip8x;
+pub mod font;
+pub mod idle;
+pub mod inline;
+#[cfg(feature = "jit")]
+pub mod jit;
+pub mod keymap;
+pub mod memory;
+pub mod palette;
+pub mod phosphor;
+pub mod platform;
+#[cfg(feature = "capture")]
+pub mod recording;
+pub mod scale;
+#[cfg(feature = "capture")]
+pub mod screenshot;
+pub mod state;
+pub mod stream;
+pub mod timing;
+
+use chip8x::IoPort;
+use font::Font;
+use idle::Idle;
+use memory::{MemoryMap, RomError};
+use palette::Palette;
+use platform::Platform;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::fs;
+use timing::TimingModel;
+
+// Function that executes one decoded instruction
+pub type Handler = fn(&mut CHIP8, u16);
+
+// Function that runs the basic block at the PC, if it knows one
+// Takes the most instructions and the VIP machine cycles the block may use, and returns how many
+// of each it used. Returning None leaves the instruction to the interpreter.
+pub type BlockRunner = fn(&mut CHIP8, u32, i64) -> Option<(u32, i64)>;
+
+// CHIP-8 SPECIFICS
+#[allow(non_snake_case)]
+pub struct CHIP8 {
+    pub registers: [u8; 16], // 16 8-bit Registers
+    pub memory: Vec<u8>,     // 4K Bytes of Memory (size depends on the memory map)
+    pub IR: u16, // 16-bit Index Register (16 bits are needed to hold the maximum memory adress 0xFFF)
+    pub PC: u16, // 16-bit Program Counter
+    pub stack: [u16; 16], // 16 level Execution Stack
+    pub st_pointer: usize, // 8-bit Stack Pointer
+    pub delay_timer: u8, // 8-bit Delay Timer
+    pub sound_timer: u8, // 8-bit Sound Timer
+    pub keypad: [bool; 16], // 16 input keys
+    pub video: Vec<u64>, // 64 by 32 pixels video screen (64 by 64 in hi-res mode), one row per u64
+    pub display_dirty: bool, // Set when the display changes; cleared by whoever presents it
+    pub palette: Palette, // Colours the display is shown in
+    // opcode: u16, // 2 Byte operation code
+    pub platform: Platform,    // Interpreter variant being emulated
+    pub memory_map: MemoryMap, // Load address, memory size and font location
+    pub font: Font,            // Character sprites used by Fx29 and Fx30
+    pub vip_layout: bool,      // Keep the stack and display in memory like the VIP interpreter
+    pub timing: TimingModel,   // How many instructions run in each frame
+    pub frame_count: u64,      // Frames run since the ROM was loaded
+    cycle_budget: i64,         // VIP machine cycles left over (or overspent) from the last frame
+    pub display_wait: bool,    // Make Dxyn wait for the vertical blank like the VIP interpreter
+    vblank: bool,              // Set at the start of every frame, taken by the first Dxyn
+    waiting_for_vblank: bool,  // Dxyn is stalled until the next frame
+    pub idle: Idle,            // Set when the program is stuck in a loop for the rest of the frame
+    loop_state: Option<(u16, [u8; 16], u16, usize)>, // PC, V0-VF, I and SP at the last backward jump
+    side_effect: bool, // Something other than V0-VF, I or SP changed since the last backward jump
+    pub use_decode_cache: bool, // Reuse decoded instructions instead of decoding them every time
+    decode_cache: Vec<Option<(u16, Handler)>>, // Opcode and handler of the instruction at each address
+    rng: SmallRng,                             // Random numbers for Cxkk
+    #[cfg(feature = "jit")]
+    pub jit: Option<Box<jit::Jit>>, // Native code for basic blocks, when enabled
+    pub recompiled: Option<BlockRunner>,       // Blocks recompiled ahead of time from the ROM
+
+    // CHIP-8X hardware
+    pub keypad2: [bool; 16],       // 16 keys of the VP-580 second keypad
+    pub background: usize,         // Index into the 02A0 background colour cycle
+    pub color_zones: [u8; 8 * 32], // Foreground colour of every 8 pixel zone on each row
+    pub io_port: Option<Box<dyn IoPort>>, // Host callback for the FxF8 / FxFB I/O port
+}
+
+// Instructions are stored starting at address 0x200
+const START_ADDRESS: u16 = 0x200;
+
+// Fontset Size
+const FONTSET_SIZE: u8 = 80;
+// Fontset Address (Fontsets begin to be stored in 0x50, in memory)
+const FONTSET_ADDRESS: u8 = 0x50;
+// Every 5 bytes represents a 'sprite', for a total of 16 haracters
+const FONTSET: [u8; FONTSET_SIZE as usize] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+impl CHIP8 {
+    // Constructor to create a new chip8 model
+    pub fn new() -> Self {
+        let mut chip8: CHIP8 = CHIP8 {
+            registers: [0x00; 16],
+            memory: vec![0x00; MemoryMap::CHIP8.ram_size],
+            PC: START_ADDRESS, // Program Counter set to First Instruction
+            video: vec![0; Platform::Chip8.height()],
+            display_dirty: true,
+            palette: Palette::default(),
+            IR: 0,
+            stack: [0; 16],
+            st_pointer: 0,
+            delay_timer: 0,
+            sound_timer: 0,
+            platform: Platform::Chip8,
+            memory_map: MemoryMap::CHIP8,
+            font: Font::default(),
+            vip_layout: false,
+            timing: TimingModel::Vip,
+            frame_count: 0,
+            cycle_budget: 0,
+            display_wait: false,
+            vblank: false,
+            waiting_for_vblank: false,
+            idle: Idle::Running,
+            loop_state: None,
+            side_effect: false,
+            use_decode_cache: true,
+            decode_cache: vec![None; MemoryMap::CHIP8.ram_size],
+            rng: SmallRng::from_os_rng(),
+            #[cfg(feature = "jit")]
+            jit: None,
+            recompiled: None,
+            keypad: [false; 16],
+            keypad2: [false; 16],
+            background: 0,
+            color_zones: [chip8x::DEFAULT_FOREGROUND; 8 * 32],
+            io_port: None,
+        };
+
+        // Start loading the font bytes into memory, starting from 0x50
+        chip8.load_font();
+
+        // Return the newly constructed chip
+        chip8
+    }
+
+    // Copy the font into memory at the address given by the memory map
+    // The large characters follow straight after the 16 small ones
+    fn load_font(&mut self) {
+        let font_address: usize = self.memory_map.font_address as usize;
+        for (i, font) in self.font.small.iter().chain(&self.font.large).enumerate() {
+            self.memory[font_address + i] = *font;
+        }
+        self.clear_decode_cache();
+    }
+
+    // Forget every decoded instruction
+    // Needed after changing `memory` directly rather than through an instruction
+    pub fn clear_decode_cache(&mut self) {
+        self.decode_cache = vec![None; self.memory.len()];
+    }
+
+    // Forget the decoded instructions overlapping 'len' bytes of memory at 'address'
+    // That includes the instruction starting one byte earlier, whose second byte is at 'address'
+    fn invalidate(&mut self, address: usize, len: usize) {
+        let size: usize = self.decode_cache.len();
+        for i in 0..=len {
+            self.decode_cache[(address + size - 1 + i) % size] = None;
+        }
+    }
+
+    // Make Cxkk produce the same sequence of random numbers on every run
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = SmallRng::seed_from_u64(seed);
+    }
+
+    // Switch to another font and copy it into memory
+    pub fn set_font(&mut self, font: Font) {
+        self.font = font;
+        self.load_font();
+    }
+
+    // Switch the chip to another interpreter variant
+    // The display is resized and cleared, and the PC is moved to the variant's entry point
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.platform = platform;
+        self.video = vec![0; platform.height()];
+        self.display_dirty = true;
+        self.PC = platform.entry_point(self.memory_map.load_address);
+        // Some opcodes decode differently on another platform
+        self.clear_decode_cache();
+    }
+
+    // Switch the chip to another memory layout
+    // Memory is cleared and the font reloaded, and the PC is moved to the new entry point
+    pub fn set_memory_map(&mut self, memory_map: MemoryMap) {
+        self.memory_map = memory_map;
+        self.memory = vec![0x00; memory_map.ram_size];
+        self.load_font();
+        self.PC = self.platform.entry_point(memory_map.load_address);
+    }
+
+    // Address of a stack entry when the stack is kept in memory
+    // Entries are pushed downwards from 0xECE (on a 4K machine), high byte first
+    fn stack_address(&self, level: usize) -> usize {
+        self.memory.len() - memory::VIP_STACK_OFFSET - level * 2
+    }
+
+    // Address of the display when it is kept in memory
+    // The display takes the last 256 bytes (0xF00-0xFFF), or the last 512 bytes in hi-res mode
+    fn display_address(&self) -> usize {
+        self.memory.len() - self.video.len() * 8
+    }
+
+    // Copy the display into memory, 8 pixels per byte with the leftmost pixel in the high bit
+    // That is the same order the rows are packed in, so every row is stored big-endian
+    fn store_display(&mut self) {
+        if !self.vip_layout {
+            return;
+        }
+
+        let display_address: usize = self.display_address();
+        for (i, row) in self.video.iter().enumerate() {
+            let address: usize = display_address + i * 8;
+            self.memory[address..address + 8].copy_from_slice(&row.to_be_bytes());
+        }
+        self.invalidate(display_address, self.video.len() * 8);
+    }
+
+    // Redraw the display from the copy kept in memory
+    fn fetch_display(&mut self) {
+        let display_address: usize = self.display_address();
+        for (i, row) in self.video.iter_mut().enumerate() {
+            let address: usize = display_address + i * 8;
+            *row = u64::from_be_bytes(self.memory[address..address + 8].try_into().unwrap());
+        }
+        self.display_dirty = true;
+    }
+
+    // Called after an instruction writes 'len' bytes of memory starting at 'address'
+    // Writes into the display area show up on screen, as they do on the VIP
+    fn memory_written(&mut self, address: usize, len: usize) {
+        self.side_effect = true;
+        self.invalidate(address, len);
+        if self.vip_layout && address + len > self.display_address() {
+            self.fetch_display();
+        }
+    }
+
+    // Function to load a ROM File using a file name
+    // The platform is detected from the ROM unless one is given, and the memory map defaults to
+    // the platform's own layout
+    pub fn load_rom(
+        chip8: &mut CHIP8,
+        filename_path: &String,
+        platform: Option<Platform>,
+        memory_map: Option<MemoryMap>,
+    ) -> Result<(), RomError> {
+        // The ROM file is a binary file
+        // The bytes are stored in a Vector-array
+        let rom: Vec<u8> = fs::read(filename_path)?;
+        CHIP8::load_rom_bytes(chip8, &rom, platform, memory_map)
+    }
+
+    // Load a ROM that is already in memory, e.g. in a browser where there are no files
+    pub fn load_rom_bytes(
+        chip8: &mut CHIP8,
+        rom: &[u8],
+        platform: Option<Platform>,
+        memory_map: Option<MemoryMap>,
+    ) -> Result<(), RomError> {
+        // Pick the interpreter variant the ROM was written for
+        let platform: Platform = platform.unwrap_or_else(|| Platform::detect(rom));
+        let memory_map: MemoryMap = memory_map.unwrap_or(platform.memory_map());
+        memory_map.validate().map_err(RomError::MemoryMap)?;
+        if chip8.vip_layout && memory_map.ram_size < memory::VIP_WORK_AREA_SIZE + 0x100 {
+            return Err(RomError::MemoryMap(format!(
+                "{} bytes of memory are too few to hold the VIP work area",
+                memory_map.ram_size
+            )));
+        }
+        // The two display pages of the hi-res interpreter would cover the stack area
+        if chip8.vip_layout && platform == Platform::HiRes {
+            return Err(RomError::MemoryMap(String::from(
+                "the VIP layout only supports the 64 by 32 display",
+            )));
+        }
+
+        // The ROM has to fit between the load address and the end of memory
+        let load_address: usize = memory_map.load_address as usize;
+        let available: usize = memory_map.ram_size - load_address;
+        if rom.len() > available {
+            return Err(RomError::TooLarge {
+                size: rom.len(),
+                available,
+            });
+        }
+
+        chip8.set_memory_map(memory_map);
+        chip8.set_platform(platform);
+
+        // Store the instructions from the vector-array in the chip's memory starting from the
+        // load address (0x200 for most programs)
+        chip8.memory[load_address..load_address + rom.len()].copy_from_slice(rom);
+        chip8.clear_decode_cache();
+        chip8.frame_count = 0;
+
+        Ok(())
+    }
+
+    // Whether the pixel at (x, y) is lit
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.video[y] & (1 << (63 - x)) != 0
+    }
+
+    // Build the colour image of the screen
+    pub fn frame(&self) -> Vec<u32> {
+        let mut buffer: Vec<u32> = vec![0; self.platform.width() * self.platform.height()];
+        self.render(&mut buffer);
+        buffer
+    }
+
+    // Expand the packed display into colours, one u32 per pixel
+    // CHIP-8X pixels take the colour of their zone on top of the background colour, and other
+    // platforms use the palette
+    pub fn render(&self, buffer: &mut [u32]) {
+        let width: usize = self.platform.width();
+        let background: u32 = match self.platform {
+            Platform::Chip8X => chip8x::BACKGROUNDS[self.background],
+            _ => self.palette.colors[0],
+        };
+
+        for (y, pixels) in buffer.chunks_mut(width).enumerate() {
+            let row: u64 = self.video[y];
+            for (x, pixel) in pixels.iter_mut().enumerate() {
+                *pixel = if row & (1 << (63 - x)) == 0 {
+                    background
+                } else if self.platform == Platform::Chip8X {
+                    let zone: usize = y * 8 + x / chip8x::ZONE_WIDTH;
+                    chip8x::COLORS[self.color_zones[zone] as usize]
+                } else {
+                    self.palette.colors[1]
+                };
+            }
+        }
+    }
+
+    // 00E0 - CLS, and 0230 on hi-res
+    // Clear the video display
+    // On the hi-res platform the whole 64 by 64 display is cleared, both pages of it. Hi-res
+    // programs clear it by calling the interpreter patch at 0x230 instead, which does the same.
+    fn op_00e0(&mut self) {
+        trace!("In OP_00E0");
+        // Set all pixels in the screen to 0 (black)
+        self.video.fill(0);
+        self.display_dirty = true;
+        self.store_display();
+        self.side_effect = true;
+    }
+
+    // 02A0 - BGCOL (CHIP-8X)
+    // Step the background colour to the next one in the cycle (blue, black, green, red)
+    fn op_02a0(&mut self) {
+        trace!("In OP_02A0");
+        self.background = (self.background + 1) % chip8x::BACKGROUNDS.len();
+        self.display_dirty = true;
+        self.side_effect = true;
+    }
+
+    // 00EE - RET
+    // Return from a subroutine
+    fn op_00ee(&mut self) {
+        // The top of the stack has the address of one instruction past the one that called the subroutine
+        // So we can put that back into the PC.
+        trace!("In OP_00EE");
+        // A return without a call wraps around to the top of the 16 entry stack
+        self.st_pointer = (self.st_pointer + self.stack.len() - 1) % self.stack.len();
+
+        // Programs may have changed the return address in memory
+        if self.vip_layout {
+            let address: usize = self.stack_address(self.st_pointer);
+            self.stack[self.st_pointer] =
+                u16::from_be_bytes([self.memory[address], self.memory[address + 1]]);
+        }
+
+        self.PC = self.stack[self.st_pointer];
+    }
+
+    // 1nnn - JP addr
+    // Jump to location at 'nnn'
+    fn op_1nnn(&mut self, opcode: u16) {
+        trace!("In OP_1NNN");
+        // Mask the opcode to retrieve the address
+        let address: u16 = opcode & 0x0FFF;
+
+        // Set PC to address
+        self.PC = address;
+    }
+
+    // 2nnn - CALL addr
+    // Call subroutine at 'nnn'
+    fn op_2nnn(&mut self, opcode: u16) {
+        trace!("In OP_2NNN");
+
+        // Mask the opcode to retrieve the address
+        let address: u16 = opcode & 0x0FFF;
+
+        // Push the current PC on top of the stack
+        self.stack[self.st_pointer] = self.PC;
+        if self.vip_layout {
+            let address: usize = self.stack_address(self.st_pointer);
+            self.memory[address..address + 2].copy_from_slice(&self.PC.to_be_bytes());
+            self.invalidate(address, 2);
+        }
+        // Increment the stack pointer
+        // A 17th nested call wraps around to the bottom of the stack and overwrites the first entry
+        self.st_pointer = (self.st_pointer + 1) % self.stack.len();
+        // Set the PC to the address
+        self.PC = address;
+    }
+
+    // 3xkk - SE Vx, byte
+    // Skip next instruction if Vx = kk
+    //The interpreter compares register Vx to kk, and if they are equal, increments the program counter by 2.
+    fn op_3xkk(&mut self, opcode: u16) {
+        trace!("In OP_3XKK");
+
+        // Mask the opcode to get the first 8 bits, which represent 'kk'
+        let value: u16 = opcode & 0x00FF;
+        // Bitshift to the right by 8 bits, then mask the first 4 bits, which represent 'x'
+        let r_address: u16 = (opcode & 0x0F00) >> 8;
+
+        // Compare if Vx and kk are equal
+        if self.registers[r_address as usize] == value as u8 {
+            self.advance_pc(2);
+        }
+    }
+
+    // 4xkk - SNE Vx, byte
+    // Skip next instruction if Vx != kk
+    // The interpreter compares register Vx to kk, and if they are not equal, increments the program counter by 2.
+    fn op_4xkk(&mut self, opcode: u16) {
+        trace!("In OP_4XKK");
+
+        // Mask the opcode to get the first 8 bits, which represent 'kk'
+        let value: u16 = opcode & 0x00FF;
+        // Bitshift to the right by 8 bits, then mask the first 4 bits, which represent 'x'
+        let r_address: u16 = (opcode & 0x0F00) >> 8;
+
+        // Check Vx and kk are not equal
+        if self.registers[r_address as usize] != value as u8 {
+            self.advance_pc(2);
+        }
+    }
+
+    // 5xy0 - SE Vx, Vy
+    // Skip next instruction if Vx = Vy.
+    // The interpreter compares register Vx to register Vy, and if they are equal, increments the program counter by 2.
+    fn op_5xy0(&mut self, opcode: u16) {
+        trace!("In OP_5XY0");
+
+        // Bitshift the opcode 4 bits to the right to remove the '0', then mask to get 0x00y
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        // Bitshift the opcode 8 bits to the right to remove the 'y0', then mask to get 0x0x
+        let y: u16 = (opcode & 0x00F0) >> 4;
+
+        // Compare if Vx and Vy are equal
+        if self.registers[x as usize] == self.registers[y as usize] {
+            self.advance_pc(2);
+        }
+    }
+
+    // 5xy1 - ADD Vx, Vy (CHIP-8X)
+    // Set Vx = Vx + Vy, adding the two octal digits in each nibble separately.
+    // Bits 3 and 7 are dropped, so the additions wrap around without carrying into each other.
+    fn op_5xy1(&mut self, opcode: u16) {
+        trace!("In OP_5XY1");
+        // Bitshift the opcode 8 bits to the right, then mask to get 0x0x
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        // Bitshift the opcode 4 bits to the right, then mask to get 0x00y
+        let y: u16 = (opcode & 0x00F0) >> 4;
+        // Receive the values from the respective registers
+        let vx: u8 = self.registers[x as usize];
+        let vy: u8 = self.registers[y as usize];
+
+        self.registers[x as usize] = ((vx & 0x77) + (vy & 0x77)) & 0x77;
+    }
+
+    // 6xkk - LD Vx, byte
+    // Set Vx = kk.
+    // The interpreter puts the value kk into register Vx.
+    fn op_6xkk(&mut self, opcode: u16) {
+        trace!("In OP_6XKK");
+
+        // Mask the opcode to get 0x00kk
+        let value: u16 = opcode & 0x00FF;
+        // Bitwise shift to the right by 8 bits, then mask to get 0x0x
+        let r_address: u16 = (opcode & 0x0F00) >> 8;
+        // Load 'kk' into 'Vx'
+        self.registers[r_address as usize] = value as u8;
+
+        trace!(
+            "Out OP_6XKK\nVALUE: {:#x}\nREGISTER: {:#x}\n",
+            value, r_address
+        );
+    }
+
+    // 7xkk - ADD Vx, byte
+    // Set Vx = Vx + kk.
+    // Adds the value kk to the value of register Vx, then stores the result in Vx.
+    fn op_7xkk(&mut self, opcode: u16) {
+        trace!("In OP_7XKK");
+
+        // Mask the opcode to get 0x00kk
+        let value: u16 = opcode & 0x00FF;
+        // Bitwise shift to the right by 8 bits, then mask to get 0x0x
+        let r_address: u16 = (opcode & 0x0F00) >> 8;
+        // Add 'kk' into 'Vx'
+        let result = u8::overflowing_add(self.registers[r_address as usize], value as u8);
+
+        self.registers[r_address as usize] = result.0;
+    }
+
+    // 8xy0 - LD Vx, Vy
+    // Set Vx = Vy.
+    // Stores the value of register Vy in register Vx.
+    fn op_8xy0(&mut self, opcode: u16) {
+        trace!("In OP_8XY0");
+
+        // Bitshift the opcode 4 bits to the right to remove the '0', then mask to get 0x00y
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        // Bitshift the opcode 8 bits to the right to remove the 'y0', then mask to get 0x0x
+        let y: u16 = (opcode & 0x00F0) >> 4;
+        // Load the value inside 'Vy' onto 'Vx'
+        self.registers[x as usize] = self.registers[y as usize];
+    }
+
+    // 8xy1 - OR Vx, Vy
+    // Set Vx = Vx OR Vy.
+    // Performs a bitwise OR on the values of Vx and Vy, then stores the result in Vx.
+    fn op_8xy1(&mut self, opcode: u16) {
+        trace!("In OP_8XY1");
+        // Bitshift the opcode 4 bits to the right to remove the '0', then mask to get 0x00y
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        // Bitshift the opcode 8 bits to the right to remove the 'y0', then mask to get 0x0x
+        let y: u16 = (opcode & 0x00F0) >> 4;
+        // Perform bitwise OR with the values inside 'Vx' and 'Vy'. Store back into 'Vx'
+        self.registers[x as usize] |= self.registers[y as usize];
+    }
+
+    // 8xy2 - AND Vx, Vy
+    // Set Vx = Vx AND Vy.
+    // Performs a bitwise AND on the values of Vx and Vy, then stores the result in Vx.
+    fn op_8xy2(&mut self, opcode: u16) {
+        trace!("In OP_8XY2");
+        // Bitshift the opcode 4 bits to the right to remove the '0', then mask to get 0x00y
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        // Bitshift the opcode 8 bits to the right to remove the 'y0', then mask to get 0x0x
+        let y: u16 = (opcode & 0x00F0) >> 4;
+        // Perform bitwise AND with the values inside 'Vx' and 'Vy'. Store back into 'Vx'
+        self.registers[x as usize] &= self.registers[y as usize];
+    }
+
+    // 8xy3 - XOR Vx, Vy
+    // Set Vx = Vx XOR Vy.
+    // Performs a bitwise exclusive OR on the values of Vx and Vy, then stores the result in Vx.
+    fn op_8xy3(&mut self, opcode: u16) {
+        trace!("In OP_8XY3");
+        // Bitshift the opcode 4 bits to the right to remove the '0', then mask to get 0x00y
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        // Bitshift the opcode 8 bits to the right to remove the 'y0', then mask to get 0x0x
+        let y: u16 = (opcode & 0x00F0) >> 4;
+        // Perform bitwise XOR with the values inside 'Vx' and 'Vy'. Store back into 'Vx'
+        self.registers[x as usize] ^= self.registers[y as usize];
+    }
+
+    // 8xy4 - ADD Vx, Vy
+    // Set Vx = Vx + Vy, set VF = carry.
+    // The values of Vx and Vy are added together.
+    fn op_8xy4(&mut self, opcode: u16) {
+        trace!("In OP_8XY4");
+        // Bitshift the opcode 4 bits to the right to remove the '0', then mask to get 0x00y
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        // Bitshift the opcode 8 bits to the right to remove the 'y0', then mask to get 0x0x
+        let y: u16 = (opcode & 0x00F0) >> 4;
+        // Receive the values from the respective registers
+        let vx: u8 = self.registers[x as usize];
+        let vy: u8 = self.registers[y as usize];
+
+        // If the result is greater than 8 bits (i.e., > 255,) VF is set to 1, otherwise 0. Only the lowest 8 bits of the result are kept, and stored in Vx.
+        let add_result = u8::checked_add(vx, vy);
+        match add_result {
+            Some(value) => self.registers[x as usize] = value,
+            None => {
+                self.registers[x as usize] = vx.wrapping_add(vy);
+                self.registers[0xF] = 1;
+            }
+        }
+    }
+    // 8xy5 - SUB Vx, Vy
+    // Set Vx = Vx - Vy, set VF = NOT borrow.
+    // The value of Vy is subtracted from Vx.
+    fn op_8xy5(&mut self, opcode: u16) {
+        trace!("In OP_8XY5");
+        // Bitshift the opcode 4 bits to the right to remove the '0', then mask to get 0x00y
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        // Bitshift the opcode 8 bits to the right to remove the 'y0', then mask to get 0x0x
+        let y: u16 = (opcode & 0x00F0) >> 4;
+        // Receive the values from the respective registers
+        let vx: u8 = self.registers[x as usize];
+        let vy: u8 = self.registers[y as usize];
+
+        // If Vx > Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from Vx, and the results stored in Vx.
+        self.registers[x as usize] = vx.wrapping_sub(vy);
+        if vx > vy {
+            self.registers[0xF] = 1;
+        } else {
+            self.registers[0xF] = 0;
+        }
+    }
+
+    // 8xy6 - SHR Vx {, Vy}
+    // Set Vx = Vx SHR 1.
+    // fn op_8xy6(&mut self, opcode: u16) {
+    //     // Bitshift the opcode 4 bits to the right to remove the '0', then mask to get 0x00y
+    //     let x: u16 = (opcode >> 4) & 0x00F;
+    //     // Bitshift the opcode 8 bits to the right to remove the 'y0', then mask to get 0x0x
+    //     let y: u16 = (opcode >> 8) & 0x0F;
+    // }
+
+    // 8xy7 - SUBN Vx, Vy
+    // Set Vx = Vy - Vx, set VF = NOT borrow.
+    // The value of Vx is substracted from Vy.
+    fn op_8xy7(&mut self, opcode: u16) {
+        trace!("In OP_8XY7");
+        // Bitshift the opcode 4 bits to the right to remove the '0', then mask to get 0x00y
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        // Bitshift the opcode 8 bits to the right to remove the 'y0', then mask to get 0x0x
+        let y: u16 = (opcode & 0x00F0) >> 4;
+        // Receive the values from the respective registers
+        let vx: u8 = self.registers[x as usize];
+        let vy: u8 = self.registers[y as usize];
+
+        // If Vy > Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and the results stored in Vx.
+        self.registers[x as usize] = vy.wrapping_sub(vx);
+        if vy > vx {
+            self.registers[15] = 1;
+        } else {
+            self.registers[15] = 0;
+        }
+    }
+
+    // 8xyE - SHL Vx {, Vy}
+    // Set Vx = Vx SHL 1.
+
+    // 9xy0 - SNE Vx, Vy
+    // Skip next instruction if Vx != Vy.
+    fn op_9xy0(&mut self, opcode: u16) {
+        trace!("In OP_9XY0");
+        // Bitshift the opcode 4 bits to the right to remove the '0', then mask to get 0x00y
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        // Bitshift the opcode 8 bits to the right to remove the 'y0', then mask to get 0x0x
+        let y: u16 = (opcode & 0x00F0) >> 4;
+
+        // Compare if Vx and Vy are not equal
+        if self.registers[x as usize] != self.registers[y as usize] {
+            self.advance_pc(2);
+        }
+    }
+
+    // Annn - LD I, addr
+    // Set I = nnn.
+    fn op_annn(&mut self, opcode: u16) {
+        trace!("In OP_ANNN");
+        // The value of register I is set to nnn.
+        self.IR = opcode & 0x0FFF;
+        trace!("Out OP_ANNN\nIR: {:#x}", self.IR);
+    }
+
+    // Bnnn - JP V0, addr
+    // Jump to location nnn + V0.
+    fn op_bnnn(&mut self, opcode: u16) {
+        trace!("In OP_BNNN");
+        // The program counter is set to nnn plus the value of V0.
+        self.PC = self.registers[0] as u16 + (opcode & 0x0FFF);
+    }
+
+    // Bxy0 - COL Vx, Vy (CHIP-8X)
+    // Set the foreground colour of an area of 8 by 4 pixel zones to the colour in Vy.
+    // The low nibble of Vx is the leftmost zone and the high nibble the number of extra zones to
+    // the right; Vx+1 gives the topmost zone and extra zones downwards in the same way.
+    fn op_bxy0(&mut self, opcode: u16) {
+        trace!("In OP_BXY0");
+        let x: usize = ((opcode & 0x0F00) >> 8) as usize;
+        let y: usize = ((opcode & 0x00F0) >> 4) as usize;
+
+        let horizontal: u8 = self.registers[x];
+        let vertical: u8 = self.registers[(x + 1) & 0xF];
+        let color: u8 = self.registers[y] & 0x7;
+
+        let columns: usize = self.platform.width() / chip8x::ZONE_WIDTH;
+        let zone_rows: usize = self.platform.height() / chip8x::ZONE_HEIGHT;
+
+        let left: usize = (horizontal & 0x0F) as usize;
+        let right: usize = left + (horizontal >> 4) as usize;
+        let top: usize = (vertical & 0x0F) as usize;
+        let bottom: usize = top + (vertical >> 4) as usize;
+
+        // Zones outside the screen are ignored
+        for zone_row in top..=bottom.min(zone_rows - 1) {
+            for row in zone_row * chip8x::ZONE_HEIGHT..(zone_row + 1) * chip8x::ZONE_HEIGHT {
+                for column in left..=right.min(columns - 1) {
+                    self.color_zones[row * columns + column] = color;
+                }
+            }
+        }
+        self.display_dirty = true;
+        self.side_effect = true;
+    }
+
+    // Bxyn - COL Vx, Vy, nibble (CHIP-8X)
+    // Set the foreground colour of n single-row zones to the colour in Vy.
+    // Vx is the horizontal pixel position of the zone and Vx+1 the first row.
+    fn op_bxyn(&mut self, opcode: u16) {
+        trace!("In OP_BXYN");
+        let x: usize = ((opcode & 0x0F00) >> 8) as usize;
+        let y: usize = ((opcode & 0x00F0) >> 4) as usize;
+        let height: usize = (opcode & 0x000F) as usize;
+
+        let columns: usize = self.platform.width() / chip8x::ZONE_WIDTH;
+        let column: usize =
+            (self.registers[x] as usize % self.platform.width()) / chip8x::ZONE_WIDTH;
+        let top: usize = self.registers[(x + 1) & 0xF] as usize % self.platform.height();
+        let color: u8 = self.registers[y] & 0x7;
+
+        // Rows past the bottom of the screen are ignored
+        for row in top..(top + height).min(self.platform.height()) {
+            self.color_zones[row * columns + column] = color;
+        }
+        self.display_dirty = true;
+        self.side_effect = true;
+    }
+
+    // Cxkk - RND Vx, byte
+    // Set Vx = random byte AND kk.
+    fn op_cxkk(&mut self, opcode: u16) {
+        trace!("In OP_CXKK");
+        let value: u16 = opcode & 0x00FF;
+        let r_address: u16 = (opcode & 0x0F00) >> 8;
+        // The interpreter generates a random number from 0 to 255, which is then ANDed with the value kk. The results are stored in Vx.
+        self.registers[r_address as usize] = (self.rng.random::<u16>() & value) as u8;
+        // A loop drawing random numbers may be waiting for a particular one
+        self.side_effect = true;
+    }
+
+    //     Dxyn - DRW Vx, Vy, nibble
+    // Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
+
+    // The interpreter reads n bytes from memory, starting at the address stored in I. These bytes are then displayed as sprites on screen at coordinates (Vx, Vy). Sprites are XORed onto the existing screen. If this causes any pixels to be erased, VF is set to 1, otherwise it is set to 0. If the sprite is positioned so part of it is outside the coordinates of the display, it wraps around to the opposite side of the screen.
+    fn op_dxyn(&mut self, opcode: u16) {
+        trace!("In OP_DXYN");
+
+        // The VIP interpreter waits for the vertical blank before drawing, so only one sprite
+        // can be drawn per frame. Until then the instruction is run again next frame.
+        if self.display_wait {
+            if !self.vblank {
+                self.advance_pc(-2);
+                self.waiting_for_vblank = true;
+                return;
+            }
+            self.vblank = false;
+        }
+
+        let height: u8 = (opcode & 0x000F) as u8;
+        let vy: u16 = (opcode & 0x00F0) >> 4;
+        let vx: u16 = (opcode & 0x0F00) >> 8;
+
+        // The display is 64 by 32, or 64 by 64 when running hi-res programs
+        let screen_width: usize = self.platform.width();
+        let screen_height: usize = self.platform.height();
+
+        // The starting position wraps around the screen
+        let x_pos: usize = self.registers[vx as usize] as usize % screen_width;
+        let y_pos: usize = self.registers[vy as usize] as usize % screen_height;
+
+        let mut collision: bool = false;
+        for row in 0..height as usize {
+            // Rows past the bottom of the screen are clipped
+            if y_pos + row >= screen_height {
+                break;
+            }
+
+            let sprite_byte: u8 = self.memory[(self.IR as usize + row) % self.memory.len()];
+            // Line the sprite up with the row; columns past the right edge are shifted out
+            let sprite_row: u64 = ((sprite_byte as u64) << 56) >> x_pos;
+
+            collision |= self.video[y_pos + row] & sprite_row != 0;
+            self.video[y_pos + row] ^= sprite_row;
+        }
+        self.registers[0xF] = collision as u8;
+
+        self.display_dirty = true;
+        self.store_display();
+        self.side_effect = true;
+
+        trace!("OUT OP_DXYN");
+    }
+
+    // Ex9E - SKP Vx
+    // Skip next instruction if the key with the value of Vx is pressed.
+    fn op_ex9e(&mut self, opcode: u16) {
+        trace!("In OP_EX9E");
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        let key: u8 = self.registers[x as usize] & 0xF;
+
+        if self.keypad[key as usize] {
+            self.advance_pc(2);
+        }
+    }
+
+    // ExA1 - SKNP Vx
+    // Skip next instruction if the key with the value of Vx is not pressed.
+    fn op_exa1(&mut self, opcode: u16) {
+        trace!("In OP_EXA1");
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        let key: u8 = self.registers[x as usize] & 0xF;
+
+        if !self.keypad[key as usize] {
+            self.advance_pc(2);
+        }
+    }
+
+    // ExF2 - SKP2 Vx (CHIP-8X)
+    // Skip next instruction if the key with the value of Vx is pressed on the second keypad.
+    fn op_exf2(&mut self, opcode: u16) {
+        trace!("In OP_EXF2");
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        let key: u8 = self.registers[x as usize] & 0xF;
+
+        if self.keypad2[key as usize] {
+            self.advance_pc(2);
+        }
+    }
+
+    // ExF5 - SKNP2 Vx (CHIP-8X)
+    // Skip next instruction if the key with the value of Vx is not pressed on the second keypad.
+    fn op_exf5(&mut self, opcode: u16) {
+        trace!("In OP_EXF5");
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        let key: u8 = self.registers[x as usize] & 0xF;
+
+        if !self.keypad2[key as usize] {
+            self.advance_pc(2);
+        }
+    }
+
+    // Fx07 - LD Vx, DT
+    // Set Vx = delay timer value.
+    fn op_fx07(&mut self, opcode: u16) {
+        trace!("In OP_FX07");
+        let x: u16 = (opcode & 0x0F00) >> 8;
+
+        self.registers[x as usize] = self.delay_timer;
+    }
+
+    // Fx0A - LD Vx, K
+    // Wait for a key press, store the value of the key in Vx.
+    fn op_fx0a(&mut self, opcode: u16) {
+        trace!("In OP_FX0A");
+        let x: u16 = (opcode & 0x0F00) >> 8;
+
+        match self.keypad.iter().position(|pressed| *pressed) {
+            Some(key) => self.registers[x as usize] = key as u8,
+            // Run this instruction again until a key is pressed
+            None => {
+                self.advance_pc(-2);
+                self.idle = Idle::WaitingForKey;
+            }
+        }
+    }
+
+    // Fx15 - LD DT, Vx
+    // Set delay timer = Vx.
+    fn op_fx15(&mut self, opcode: u16) {
+        trace!("In OP_FX15");
+        let x: u16 = (opcode & 0x0F00) >> 8;
+
+        self.delay_timer = self.registers[x as usize];
+        self.side_effect = true;
+    }
+
+    // Fx18 - LD ST, Vx
+    // Set sound timer = Vx.
+    fn op_fx18(&mut self, opcode: u16) {
+        trace!("In OP_FX18");
+        let x: u16 = (opcode & 0x0F00) >> 8;
+
+        self.sound_timer = self.registers[x as usize];
+        self.side_effect = true;
+    }
+
+    // Fx1E - ADD I, Vx
+    // Set I = I + Vx.
+    fn op_fx1e(&mut self, opcode: u16) {
+        trace!("In OP_FX1E");
+        let x: u16 = (opcode & 0x0F00) >> 8;
+
+        self.IR = self.IR.wrapping_add(self.registers[x as usize] as u16);
+    }
+
+    // Fx29 - LD F, Vx
+    // Set I = location of sprite for digit Vx.
+    // The value of I is set to the location of the small character for the low nibble of Vx in
+    // the active font.
+    fn op_fx29(&mut self, opcode: u16) {
+        trace!("In OP_FX29");
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        let digit: u16 = (self.registers[x as usize] & 0xF) as u16;
+
+        self.IR = self.memory_map.font_address + digit * 5;
+    }
+
+    // Fx30 - LD HF, Vx
+    // Set I = location of large sprite for digit Vx.
+    // The large characters are stored after the small ones. Fonts without a large version of
+    // the digit fall back to the small character.
+    fn op_fx30(&mut self, opcode: u16) {
+        trace!("In OP_FX30");
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        let digit: usize = (self.registers[x as usize] & 0xF) as usize;
+
+        if digit < self.font.large_chars() {
+            self.IR = self.memory_map.font_address
+                + FONTSET_SIZE as u16
+                + (digit * font::LARGE_CHAR_SIZE) as u16;
+        } else {
+            self.IR = self.memory_map.font_address + digit as u16 * 5;
+        }
+    }
+
+    // Fx33 - LD B, Vx
+    // Store BCD representation of Vx in memory locations I, I+1, and I+2.
+    // The hundreds digit goes in I, the tens digit in I+1 and the ones digit in I+2.
+    fn op_fx33(&mut self, opcode: u16) {
+        trace!("In OP_FX33");
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        let value: u8 = self.registers[x as usize];
+        let address: usize = self.IR as usize;
+        let len: usize = self.memory.len();
+
+        self.memory[address % len] = value / 100;
+        self.memory[(address + 1) % len] = (value / 10) % 10;
+        self.memory[(address + 2) % len] = value % 10;
+
+        self.memory_written(address, 3);
+    }
+
+    // Fx55 - LD [I], Vx
+    // Store registers V0 through Vx in memory starting at location I.
+    // As on the VIP, I is left pointing past the last stored register.
+    fn op_fx55(&mut self, opcode: u16) {
+        trace!("In OP_FX55");
+        let x: usize = ((opcode & 0x0F00) >> 8) as usize;
+        let address: usize = self.IR as usize;
+        let len: usize = self.memory.len();
+
+        for i in 0..=x {
+            self.memory[(address + i) % len] = self.registers[i];
+        }
+        self.IR = self.IR.wrapping_add(x as u16 + 1);
+
+        self.memory_written(address, x + 1);
+    }
+
+    // Fx65 - LD Vx, [I]
+    // Read registers V0 through Vx from memory starting at location I.
+    // As on the VIP, I is left pointing past the last loaded register.
+    fn op_fx65(&mut self, opcode: u16) {
+        trace!("In OP_FX65");
+        let x: usize = ((opcode & 0x0F00) >> 8) as usize;
+        let address: usize = self.IR as usize;
+        let len: usize = self.memory.len();
+
+        for i in 0..=x {
+            self.registers[i] = self.memory[(address + i) % len];
+        }
+        self.IR = self.IR.wrapping_add(x as u16 + 1);
+    }
+
+    // FxF8 - OUT Vx (CHIP-8X)
+    // Output Vx to the I/O port.
+    fn op_fxf8(&mut self, opcode: u16) {
+        trace!("In OP_FXF8");
+        let x: u16 = (opcode & 0x0F00) >> 8;
+        let value: u8 = self.registers[x as usize];
+
+        // Nothing happens when no host device is attached
+        if let Some(port) = self.io_port.as_mut() {
+            port.output(value);
+        }
+        self.side_effect = true;
+    }
+
+    // FxFB - IN Vx (CHIP-8X)
+    // Wait for input from the I/O port and load it into Vx.
+    fn op_fxfb(&mut self, opcode: u16) {
+        trace!("In OP_FXFB");
+        let x: u16 = (opcode & 0x0F00) >> 8;
+
+        match self.io_port.as_mut().and_then(|port| port.input()) {
+            Some(value) => self.registers[x as usize] = value,
+            // Run this instruction again until the port has something to read
+            None => self.advance_pc(-2),
+        }
+    }
+
+    fn op_null(&self) {}
+
+    // TODO Finish all instructions
+    pub fn exec(&mut self, opcode: u16) {
+        trace!("In OPCODE EXECUTE STAGE; OPCODE: {:#x}", opcode);
+        trace!("MATCHIN: {:#x}", (opcode & 0xF000) >> 12);
+        match (opcode & 0xF000) >> 12 {
+            0x0 => match opcode {
+                0x00E0 => self.op_00e0(),
+                0x00EE => self.op_00ee(),
+                0x0230 if self.platform == Platform::HiRes => self.op_00e0(),
+                0x02A0 if self.platform == Platform::Chip8X => self.op_02a0(),
+                _ => self.op_null(),
+            },
+            0x1 => self.op_1nnn(opcode),
+            0x2 => self.op_2nnn(opcode),
+            0x3 => self.op_3xkk(opcode),
+            0x4 => self.op_4xkk(opcode),
+            0x5 => match opcode & 0x000F {
+                0x0 => self.op_5xy0(opcode),
+                0x1 if self.platform == Platform::Chip8X => self.op_5xy1(opcode),
+                _ => self.op_null(),
+            },
+            0x6 => self.op_6xkk(opcode),
+            0x7 => self.op_7xkk(opcode),
+            0x8 => match opcode & 0x000F {
+                0x0 => self.op_8xy0(opcode),
+                0x1 => self.op_8xy1(opcode),
+                0x2 => self.op_8xy2(opcode),
+                0x3 => self.op_8xy3(opcode),
+                0x4 => self.op_8xy4(opcode),
+                0x5 => self.op_8xy5(opcode),
+                // 0x6 => self.op_8xy6(opcode),
+                0x7 => self.op_8xy7(opcode),
+                // 0xE => self.op_8xyE(opcode),
+                _ => self.op_null(),
+            },
+            0x9 => self.op_9xy0(opcode),
+            0xA => self.op_annn(opcode),
+            // CHIP-8X replaces Bnnn with the colour opcodes
+            0xB if self.platform == Platform::Chip8X => match opcode & 0x000F {
+                0x0 => self.op_bxy0(opcode),
+                _ => self.op_bxyn(opcode),
+            },
+            0xB => self.op_bnnn(opcode),
+            0xC => self.op_cxkk(opcode),
+            0xD => self.op_dxyn(opcode),
+            0xE => match opcode & 0x00FF {
+                0x9E => self.op_ex9e(opcode),
+                0xA1 => self.op_exa1(opcode),
+                0xF2 if self.platform == Platform::Chip8X => self.op_exf2(opcode),
+                0xF5 if self.platform == Platform::Chip8X => self.op_exf5(opcode),
+                _ => self.op_null(),
+            },
+            0xF => match opcode & 0x00FF {
+                0x07 => self.op_fx07(opcode),
+                0x0A => self.op_fx0a(opcode),
+                0x15 => self.op_fx15(opcode),
+                0x18 => self.op_fx18(opcode),
+                0x1E => self.op_fx1e(opcode),
+                0x29 => self.op_fx29(opcode),
+                0x30 => self.op_fx30(opcode),
+                0x33 => self.op_fx33(opcode),
+                0x55 => self.op_fx55(opcode),
+                0x65 => self.op_fx65(opcode),
+                0xF8 if self.platform == Platform::Chip8X => self.op_fxf8(opcode),
+                0xFB if self.platform == Platform::Chip8X => self.op_fxfb(opcode),
+                _ => self.op_null(),
+            },
+            _ => self.op_null(),
+        }
+    }
+
+    // Work out which handler executes an opcode, for the decode cache
+    // This has to pick the same instruction as exec does
+    // The result depends on the platform, so cached handlers are dropped when it changes
+    pub fn decode(&self, opcode: u16) -> Handler {
+        match (opcode & 0xF000) >> 12 {
+            0x0 => match opcode {
+                0x00E0 => |chip8, _| chip8.op_00e0(),
+                0x00EE => |chip8, _| chip8.op_00ee(),
+                0x0230 if self.platform == Platform::HiRes => |chip8, _| chip8.op_00e0(),
+                0x02A0 if self.platform == Platform::Chip8X => |chip8, _| chip8.op_02a0(),
+                _ => |chip8, _| chip8.op_null(),
+            },
+            0x1 => CHIP8::op_1nnn,
+            0x2 => CHIP8::op_2nnn,
+            0x3 => CHIP8::op_3xkk,
+            0x4 => CHIP8::op_4xkk,
+            0x5 => match opcode & 0x000F {
+                0x0 => CHIP8::op_5xy0,
+                0x1 if self.platform == Platform::Chip8X => CHIP8::op_5xy1,
+                _ => |chip8, _| chip8.op_null(),
+            },
+            0x6 => CHIP8::op_6xkk,
+            0x7 => CHIP8::op_7xkk,
+            0x8 => match opcode & 0x000F {
+                0x0 => CHIP8::op_8xy0,
+                0x1 => CHIP8::op_8xy1,
+                0x2 => CHIP8::op_8xy2,
+                0x3 => CHIP8::op_8xy3,
+                0x4 => CHIP8::op_8xy4,
+                0x5 => CHIP8::op_8xy5,
+                // 0x6 => CHIP8::op_8xy6,
+                0x7 => CHIP8::op_8xy7,
+                // 0xE => CHIP8::op_8xyE,
+                _ => |chip8, _| chip8.op_null(),
+            },
+            0x9 => CHIP8::op_9xy0,
+            0xA => CHIP8::op_annn,
+            // CHIP-8X replaces Bnnn with the colour opcodes
+            0xB if self.platform == Platform::Chip8X => match opcode & 0x000F {
+                0x0 => CHIP8::op_bxy0,
+                _ => CHIP8::op_bxyn,
+            },
+            0xB => CHIP8::op_bnnn,
+            0xC => CHIP8::op_cxkk,
+            0xD => CHIP8::op_dxyn,
+            0xE => match opcode & 0x00FF {
+                0x9E => CHIP8::op_ex9e,
+                0xA1 => CHIP8::op_exa1,
+                0xF2 if self.platform == Platform::Chip8X => CHIP8::op_exf2,
+                0xF5 if self.platform == Platform::Chip8X => CHIP8::op_exf5,
+                _ => |chip8, _| chip8.op_null(),
+            },
+            0xF => match opcode & 0x00FF {
+                0x07 => CHIP8::op_fx07,
+                0x0A => CHIP8::op_fx0a,
+                0x15 => CHIP8::op_fx15,
+                0x18 => CHIP8::op_fx18,
+                0x1E => CHIP8::op_fx1e,
+                0x29 => CHIP8::op_fx29,
+                0x30 => CHIP8::op_fx30,
+                0x33 => CHIP8::op_fx33,
+                0x55 => CHIP8::op_fx55,
+                0x65 => CHIP8::op_fx65,
+                0xF8 if self.platform == Platform::Chip8X => CHIP8::op_fxf8,
+                0xFB if self.platform == Platform::Chip8X => CHIP8::op_fxfb,
+                _ => |chip8, _| chip8.op_null(),
+            },
+            _ => |chip8, _| chip8.op_null(),
+        }
+    }
+
+    // Read the instruction the PC points at
+    pub fn fetch(&self) -> u16 {
+        // Addresses wrap around the end of memory, as they do on a VIP with less than 4K fitted
+        let pc: usize = self.PC as usize % self.memory.len();
+        ((self.memory[pc] as u16 | 0xFF00) << 8) | self.memory[(pc + 1) % self.memory.len()] as u16
+    }
+
+    // Move the PC forwards, or backwards for a negative count, by a number of bytes
+    // Like fetch, it wraps around the end of memory instead of running off it
+    pub fn advance_pc(&mut self, bytes: i32) {
+        let size: i32 = self.memory.len() as i32;
+        self.PC = (self.PC as i32).wrapping_add(bytes).rem_euclid(size) as u16;
+    }
+
+    // VIP machine cycles the next instruction will take
+    pub fn instruction_cycles(&self) -> u32 {
+        let opcode: u16 = self.fetch();
+
+        // Dxyn gets slower with every row drawn and with every bit the sprite is shifted by
+        let x_pos: u8 = self.registers[((opcode & 0x0F00) >> 8) as usize];
+        let y_pos: usize =
+            self.registers[((opcode & 0x00F0) >> 4) as usize] as usize % self.platform.height();
+        let rows: usize = ((opcode & 0x000F) as usize).min(self.platform.height() - y_pos);
+
+        timing::vip_cycles(opcode, x_pos, rows as u32)
+    }
+
+    // Run the instructions for one 60 Hz frame
+    // A Dxyn waiting for the vertical blank, or an idle loop, ends the frame early
+    // The timers are ticked once at the end of the frame
+    pub fn run_frame(&mut self) {
+        // Every frame starts with a vertical blank
+        self.vblank = true;
+        self.waiting_for_vblank = false;
+        // Timers and keys may have changed since the last frame, so look again
+        self.idle = Idle::Running;
+
+        match self.timing {
+            TimingModel::InstructionsPerFrame(count) => {
+                let mut executed: u32 = 0;
+                while executed < count {
+                    match self.run_block(count - executed, i64::MAX) {
+                        Some((instructions, _)) => executed += instructions,
+                        None => {
+                            self.cycle();
+                            executed += 1;
+                        }
+                    }
+                    if self.frame_done() {
+                        break;
+                    }
+                }
+            }
+            TimingModel::Vip => {
+                // Whatever the last instruction of a frame overspent is taken off the next one
+                self.cycle_budget += timing::VIP_AVAILABLE_CYCLES;
+                while self.cycle_budget > 0 {
+                    match self.run_block(u32::MAX, self.cycle_budget) {
+                        Some((_, cycles)) => self.cycle_budget -= cycles,
+                        None => {
+                            self.cycle_budget -= self.instruction_cycles() as i64;
+                            self.cycle();
+                        }
+                    }
+                    // The rest of the frame is spent waiting
+                    if self.frame_done() {
+                        self.cycle_budget = 0;
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.tick_timers();
+        self.frame_count += 1;
+    }
+
+    // Run a recompiled or JIT compiled block at the PC instead of single instructions, when there
+    // is one
+    // Returns the number of instructions run and the VIP machine cycles they took
+    fn run_block(&mut self, max_instructions: u32, cycle_budget: i64) -> Option<(u32, i64)> {
+        if let Some(recompiled) = self.recompiled
+            && let Some(result) = recompiled(self, max_instructions, cycle_budget)
+        {
+            return Some(result);
+        }
+        self.run_jit_block(max_instructions, cycle_budget)
+    }
+
+    #[cfg(feature = "jit")]
+    fn run_jit_block(&mut self, max_instructions: u32, cycle_budget: i64) -> Option<(u32, i64)> {
+        let mut jit: Box<jit::Jit> = self.jit.take()?;
+        let result = jit.run_block(self, max_instructions, cycle_budget);
+        self.jit = Some(jit);
+        result
+    }
+
+    // Without the JIT everything else is interpreted
+    #[cfg(not(feature = "jit"))]
+    fn run_jit_block(&mut self, _max_instructions: u32, _cycle_budget: i64) -> Option<(u32, i64)> {
+        None
+    }
+
+    // Nothing more can happen before the next frame
+    fn frame_done(&self) -> bool {
+        self.waiting_for_vblank || self.idle != Idle::Running
+    }
+
+    // Fetch and decode the instruction at the PC
+    // Decoded instructions are kept per address until something writes over them
+    fn fetch_decoded(&mut self) -> (u16, Handler) {
+        let pc: usize = self.PC as usize % self.memory.len();
+        if let Some(decoded) = self.decode_cache[pc] {
+            return decoded;
+        }
+
+        let opcode: u16 = self.fetch();
+        let decoded: (u16, Handler) = (opcode, self.decode(opcode));
+        self.decode_cache[pc] = Some(decoded);
+        decoded
+    }
+
+    pub fn cycle(&mut self) {
+        let address: u16 = self.PC;
+        let opcode: u16 = if self.use_decode_cache {
+            let (opcode, handler): (u16, Handler) = self.fetch_decoded();
+            trace!("IN CYCLE STAGE; PC: {:#x} OPCODE: {:#x}\n", self.PC, opcode);
+            self.advance_pc(2);
+            handler(self, opcode);
+            opcode
+        } else {
+            let opcode: u16 = self.fetch();
+            trace!("IN CYCLE STAGE; PC: {:#x} OPCODE: {:#x}\n", self.PC, opcode);
+            self.advance_pc(2);
+            self.exec(opcode);
+            opcode
+        };
+
+        // Watch jumps backwards for loops that can't get anywhere
+        let jump: bool = match (opcode & 0xF000) >> 12 {
+            0x1 => true,
+            0xB => self.platform != Platform::Chip8X,
+            _ => false,
+        };
+        if jump && self.PC == address {
+            self.idle = Idle::Halted;
+        } else if jump && self.PC < address {
+            self.check_loop();
+        }
+    }
+
+    // Called on every backward jump
+    // If a whole trip round the loop left the state as it was, every further trip will do the
+    // same until a timer ticks or a key changes, i.e. until the next frame
+    fn check_loop(&mut self) {
+        let state = (self.PC, self.registers, self.IR, self.st_pointer);
+
+        if !self.side_effect && self.loop_state == Some(state) {
+            self.idle = Idle::Spinning;
+        }
+
+        self.loop_state = Some(state);
+        self.side_effect = false;
+    }
+
+    // Count both timers down by one, once per 60 Hz frame
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+    }
+}
+
+impl Default for CHIP8 {
+    fn default() -> Self {
+        CHIP8::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Chip with a 4K VIP memory map and the given instructions written at their addresses
+    fn chip_with(instructions: &[(usize, u16)]) -> CHIP8 {
+        let mut chip8: CHIP8 = CHIP8::new();
+        for (address, opcode) in instructions {
+            chip8.memory[*address..*address + 2].copy_from_slice(&opcode.to_be_bytes());
+        }
+        chip8.clear_decode_cache();
+        chip8
+    }
+
+    // Run the instruction at 'address'
+    fn run_at(chip8: &mut CHIP8, address: u16) {
+        chip8.PC = address;
+        chip8.cycle();
+    }
+
+    #[test]
+    fn fx33_invalidates_the_instruction_it_writes_over() {
+        // Writes 2, 5, 5 from 0x201, so 6207 (V2 = 7) becomes 6202
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0x6207), (0x300, 0xF033)]);
+        run_at(&mut chip8, 0x200);
+        assert_eq!(chip8.registers[2], 7);
+
+        chip8.registers[0] = 255;
+        chip8.IR = 0x201;
+        run_at(&mut chip8, 0x300);
+        run_at(&mut chip8, 0x200);
+        assert_eq!(chip8.registers[2], 2);
+    }
+
+    #[test]
+    fn fx55_invalidates_the_instructions_it_writes_over() {
+        // Stores 6309 (V3 = 9) over 6207
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0x6207), (0x300, 0xF155)]);
+        run_at(&mut chip8, 0x200);
+
+        chip8.registers[0] = 0x63;
+        chip8.registers[1] = 0x09;
+        chip8.IR = 0x200;
+        run_at(&mut chip8, 0x300);
+        run_at(&mut chip8, 0x200);
+        assert_eq!(chip8.registers[3], 9);
+    }
+
+    #[test]
+    fn vip_stack_push_invalidates_the_instruction_it_writes_over() {
+        // The first stack entry is at 0xECE, the second byte of 6207 at 0xECD
+        // Calling from 0x300 pushes 0x0302, which turns it into 6203
+        let mut chip8: CHIP8 = chip_with(&[(0xECD, 0x6207), (0x300, 0x2400)]);
+        chip8.vip_layout = true;
+        run_at(&mut chip8, 0xECD);
+        assert_eq!(chip8.registers[2], 7);
+
+        run_at(&mut chip8, 0x300);
+        run_at(&mut chip8, 0xECD);
+        assert_eq!(chip8.registers[2], 3);
+    }
+
+    #[test]
+    fn vip_display_store_invalidates_the_instruction_it_writes_over() {
+        // The display starts at 0xF00, the second byte of 6207 at 0xEFF
+        // Clearing the screen zeroes it, which turns it into 6200
+        let mut chip8: CHIP8 = chip_with(&[(0xEFF, 0x6207), (0x300, 0x00E0)]);
+        chip8.vip_layout = true;
+        run_at(&mut chip8, 0xEFF);
+        assert_eq!(chip8.registers[2], 7);
+
+        run_at(&mut chip8, 0x300);
+        run_at(&mut chip8, 0xEFF);
+        assert_eq!(chip8.registers[2], 0);
+    }
+
+    #[test]
+    fn stack_wraps_around_instead_of_overflowing() {
+        // A return with nothing on the stack, then 17 nested calls
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0x00EE), (0x300, 0x2300)]);
+        run_at(&mut chip8, 0x200);
+        assert_eq!(chip8.st_pointer, 15);
+
+        chip8.st_pointer = 0;
+        chip8.PC = 0x300;
+        for _ in 0..17 {
+            chip8.cycle();
+        }
+        assert_eq!(chip8.st_pointer, 1);
+    }
+
+    #[test]
+    fn pc_wraps_around_the_end_of_memory() {
+        // 8 instructions loaded at the very top of a 64K memory map
+        let mut chip8: CHIP8 = CHIP8::new();
+        let memory_map: MemoryMap = "0xFFF0:65536:0x50".parse().unwrap();
+        CHIP8::load_rom_bytes(&mut chip8, &[0x00; 16], None, Some(memory_map)).unwrap();
+        for _ in 0..12 {
+            chip8.cycle();
+        }
+        assert_eq!(chip8.PC, 0x0008);
+
+        // A skip over the last instruction in memory
+        let mut chip8: CHIP8 = CHIP8::new();
+        chip8.set_memory_map(memory_map);
+        chip8.memory[0xFFFC..0xFFFE].copy_from_slice(&0x3000u16.to_be_bytes());
+        chip8.PC = 0xFFFC;
+        chip8.cycle();
+        assert_eq!(chip8.PC, 0x0000);
+    }
+
+    #[test]
+    fn hires_entry_point_wraps_around_the_end_of_memory() {
+        let mut chip8: CHIP8 = CHIP8::new();
+        let memory_map: MemoryMap = "0xFFF0:65536:0x50".parse().unwrap();
+        CHIP8::load_rom_bytes(
+            &mut chip8,
+            &[0x00; 16],
+            Some(Platform::HiRes),
+            Some(memory_map),
+        )
+        .unwrap();
+        assert_eq!(chip8.PC, 0x00B0);
+    }
+
+    #[test]
+    fn hires_clears_both_pages_with_00e0_and_0230() {
+        for opcode in [0x00E0, 0x0230] {
+            let mut chip8: CHIP8 = chip_with(&[(0x300, opcode)]);
+            chip8.set_platform(Platform::HiRes);
+            chip8.video.fill(u64::MAX);
+            run_at(&mut chip8, 0x300);
+            assert_eq!(chip8.video, vec![0; 64]);
+        }
+    }
+
+    // Put the chip in a state where most instructions have something to do, with 'opcode' at
+    // 0x400 and 'memory' as the rest of memory
+    fn prepare(chip8: &mut CHIP8, memory: &[u8], opcode: u16) {
+        chip8.memory.copy_from_slice(memory);
+        chip8.memory[0x400..0x402].copy_from_slice(&opcode.to_be_bytes());
+        // Only the instruction at 0x400 is ever fetched
+        chip8.invalidate(0x400, 2);
+        chip8.seed(0);
+        for (i, register) in chip8.registers.iter_mut().enumerate() {
+            *register = (i * 17) as u8;
+        }
+        chip8.stack = [0; 16];
+        chip8.stack[..3].copy_from_slice(&[0x222, 0x244, 0x266]);
+        chip8.st_pointer = 3;
+        chip8.IR = 0x300;
+        chip8.delay_timer = 10;
+        chip8.sound_timer = 10;
+        chip8.keypad[0x5] = true;
+        chip8.video.fill(0x0F0F_0000_FFFF_0F0F);
+        chip8.background = 0;
+        chip8.color_zones = [chip8x::DEFAULT_FOREGROUND; 8 * 32];
+        chip8.idle = Idle::Running;
+        chip8.PC = 0x400;
+    }
+
+    // exec and the decode cache's table are separate, so every opcode is run through both
+    #[test]
+    fn decode_cache_runs_every_opcode_like_exec() {
+        for platform in [Platform::Chip8, Platform::HiRes, Platform::Chip8X] {
+            let mut exec: CHIP8 = CHIP8::new();
+            let mut decoded: CHIP8 = CHIP8::new();
+            exec.use_decode_cache = false;
+            for chip8 in [&mut exec, &mut decoded] {
+                chip8.set_platform(platform);
+            }
+            let mut memory: Vec<u8> = exec.memory.clone();
+            for (i, byte) in memory.iter_mut().enumerate().skip(0x300).take(0x100) {
+                *byte = i as u8;
+            }
+
+            for opcode in 0..=0xFFFF {
+                prepare(&mut exec, &memory, opcode);
+                prepare(&mut decoded, &memory, opcode);
+                exec.cycle();
+                decoded.cycle();
+
+                let state = |chip8: &CHIP8| {
+                    let registers = (chip8.registers, chip8.IR, chip8.PC, chip8.stack);
+                    let timers = (chip8.st_pointer, chip8.delay_timer, chip8.sound_timer);
+                    let chip8x = (chip8.background, chip8.color_zones);
+                    (registers, timers, chip8x, chip8.idle)
+                };
+                assert!(
+                    state(&exec) == state(&decoded)
+                        && exec.memory == decoded.memory
+                        && exec.video == decoded.video,
+                    "{:04X} on {:?} runs differently through the decode cache",
+                    opcode,
+                    platform
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn instruction_cycles_count_only_the_rows_on_screen() {
+        // Drawing 5 rows at y = 30 puts 2 of them on screen, shifted by 3
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0xD015)]);
+        chip8.registers[0] = 11;
+        chip8.registers[1] = 30;
+        chip8.PC = 0x200;
+        assert_eq!(chip8.instruction_cycles(), 40 + 26 + 2 * 42);
+
+        chip8.registers[1] = 0;
+        assert_eq!(chip8.instruction_cycles(), 40 + 26 + 5 * 42);
+    }
+
+    #[test]
+    fn vip_frames_carry_overspent_cycles_over() {
+        // 7001 takes 50 cycles and 1200 takes 52, out of 2598 per frame
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0x7001), (0x202, 0x1200)]);
+        chip8.timing = TimingModel::Vip;
+        chip8.PC = 0x200;
+
+        // 25 trips round the loop leave 48 cycles, and the next 7001 overspends them by 2
+        chip8.run_frame();
+        assert_eq!(
+            (chip8.registers[0], chip8.PC, chip8.cycle_budget),
+            (26, 0x202, -2)
+        );
+
+        // 2596 cycles: 25 more trips, starting with the jump, then a jump 6 cycles over
+        chip8.run_frame();
+        assert_eq!(
+            (chip8.registers[0], chip8.PC, chip8.cycle_budget),
+            (51, 0x200, -6)
+        );
+    }
+
+    #[test]
+    fn jump_to_itself_halts_the_frame() {
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0x7001), (0x202, 0x1202)]);
+        chip8.timing = TimingModel::InstructionsPerFrame(100);
+        chip8.PC = 0x200;
+        chip8.run_frame();
+        assert_eq!(
+            (chip8.idle, chip8.PC, chip8.registers[0]),
+            (Idle::Halted, 0x202, 1)
+        );
+    }
+
+    #[test]
+    fn polling_the_delay_timer_spins_until_it_runs_out() {
+        // Wait for the delay timer, then halt
+        let mut chip8: CHIP8 = chip_with(&[
+            (0x200, 0xF007),
+            (0x202, 0x3000),
+            (0x204, 0x1200),
+            (0x206, 0x1206),
+        ]);
+        chip8.timing = TimingModel::InstructionsPerFrame(100);
+        chip8.delay_timer = 3;
+        chip8.PC = 0x200;
+        for _ in 0..3 {
+            chip8.run_frame();
+            assert_eq!((chip8.idle, chip8.PC), (Idle::Spinning, 0x200));
+        }
+        chip8.run_frame();
+        assert_eq!((chip8.idle, chip8.PC), (Idle::Halted, 0x206));
+    }
+
+    #[test]
+    fn loops_that_change_something_keep_running() {
+        // Counting in V0, then setting the delay timer over and over
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0x7001), (0x202, 0x1200)]);
+        chip8.timing = TimingModel::InstructionsPerFrame(100);
+        chip8.PC = 0x200;
+        chip8.run_frame();
+        assert_eq!((chip8.idle, chip8.registers[0]), (Idle::Running, 50));
+
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0xF015), (0x202, 0x1200)]);
+        chip8.timing = TimingModel::InstructionsPerFrame(100);
+        chip8.PC = 0x200;
+        chip8.run_frame();
+        assert_eq!(chip8.idle, Idle::Running);
+    }
+
+    #[test]
+    fn fx0a_waits_for_a_key_for_the_rest_of_the_frame() {
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0xF10A), (0x202, 0x1202)]);
+        chip8.timing = TimingModel::InstructionsPerFrame(100);
+        chip8.PC = 0x200;
+        chip8.run_frame();
+        assert_eq!((chip8.idle, chip8.PC), (Idle::WaitingForKey, 0x200));
+
+        chip8.keypad[0x7] = true;
+        chip8.run_frame();
+        assert_eq!(
+            (chip8.idle, chip8.PC, chip8.registers[1]),
+            (Idle::Halted, 0x202, 7)
+        );
+    }
+
+    // Chip running CHIP-8X with the given instructions
+    fn chip8x_with(instructions: &[(usize, u16)]) -> CHIP8 {
+        let mut chip8: CHIP8 = chip_with(instructions);
+        chip8.set_platform(Platform::Chip8X);
+        chip8
+    }
+
+    #[test]
+    fn chip8x_02a0_steps_through_the_backgrounds() {
+        let mut chip8: CHIP8 = chip8x_with(&[(0x200, 0x02A0)]);
+        assert_eq!(chip8.frame()[0], 0x0000FF);
+        for background in [0x000000, 0x00FF00, 0xFF0000, 0x0000FF] {
+            run_at(&mut chip8, 0x200);
+            assert_eq!(chip8.frame(), vec![background; 64 * 32]);
+        }
+    }
+
+    #[test]
+    fn chip8x_5xy1_adds_each_octal_digit_on_its_own() {
+        let mut chip8: CHIP8 = chip8x_with(&[(0x200, 0x5011)]);
+        chip8.registers[0] = 0x36;
+        chip8.registers[1] = 0x45;
+        run_at(&mut chip8, 0x200);
+        // 3 + 4 = 7 and 6 + 5 = 3 carry 1, with the carry dropped
+        assert_eq!(chip8.registers[0], 0x73);
+    }
+
+    #[test]
+    fn chip8x_bxy0_colours_zones_of_8_by_4() {
+        // Zone columns 2 and 3 of zone row 1 (pixel rows 4 to 7) turn yellow
+        let mut chip8: CHIP8 = chip8x_with(&[(0x200, 0xB020)]);
+        chip8.registers[0] = 0x12;
+        chip8.registers[1] = 0x01;
+        chip8.registers[2] = 5;
+        run_at(&mut chip8, 0x200);
+
+        let zone = |row: usize, column: usize| chip8.color_zones[row * 8 + column];
+        for row in 4..8 {
+            assert_eq!(
+                [zone(row, 1), zone(row, 2), zone(row, 3), zone(row, 4)],
+                [1, 5, 5, 1]
+            );
+        }
+        assert_eq!([zone(3, 2), zone(8, 2)], [1, 1]);
+
+        // Lit pixels take the colour of their zone
+        chip8.video[5] = u64::MAX;
+        let frame: Vec<u32> = chip8.frame();
+        let row: &[u32] = &frame[5 * 64..6 * 64];
+        assert_eq!(
+            [row[15], row[16], row[31], row[32]],
+            [0xFF0000, 0xFFFF00, 0xFFFF00, 0xFF0000]
+        );
+        assert_eq!(frame[4 * 64 + 16], 0x0000FF);
+    }
+
+    #[test]
+    fn chip8x_bxyn_colours_single_rows_and_clips_at_the_bottom() {
+        // 5 rows from row 30 in the zone column holding x = 20
+        let mut chip8: CHIP8 = chip8x_with(&[(0x200, 0xB025)]);
+        chip8.registers[0] = 20;
+        chip8.registers[1] = 30;
+        chip8.registers[2] = 2;
+        run_at(&mut chip8, 0x200);
+
+        let mut expected: [u8; 8 * 32] = [chip8x::DEFAULT_FOREGROUND; 8 * 32];
+        expected[30 * 8 + 2] = 2;
+        expected[31 * 8 + 2] = 2;
+        assert_eq!(chip8.color_zones, expected);
+    }
+
+    #[test]
+    fn chip8x_exf2_and_exf5_read_the_second_keypad() {
+        let mut chip8: CHIP8 = chip8x_with(&[(0x200, 0xE0F2), (0x300, 0xE0F5)]);
+        chip8.registers[0] = 3;
+        chip8.keypad[3] = true;
+        run_at(&mut chip8, 0x200);
+        assert_eq!(chip8.PC, 0x202);
+        run_at(&mut chip8, 0x300);
+        assert_eq!(chip8.PC, 0x304);
+
+        chip8.keypad2[3] = true;
+        run_at(&mut chip8, 0x200);
+        assert_eq!(chip8.PC, 0x204);
+        run_at(&mut chip8, 0x300);
+        assert_eq!(chip8.PC, 0x302);
+    }
+
+    // I/O port that hands out queued input and keeps what is output
+    struct TestPort {
+        input: Vec<u8>,
+        output: std::rc::Rc<std::cell::RefCell<Vec<u8>>>,
+    }
+
+    impl IoPort for TestPort {
+        fn output(&mut self, value: u8) {
+            self.output.borrow_mut().push(value);
+        }
+
+        fn input(&mut self) -> Option<u8> {
+            self.input.pop()
+        }
+    }
+
+    #[test]
+    fn chip8x_fxf8_and_fxfb_use_the_io_port() {
+        let mut chip8: CHIP8 = chip8x_with(&[(0x200, 0xF3F8), (0x300, 0xF4FB)]);
+        chip8.registers[3] = 0x42;
+        // Without a port nothing is output and input waits
+        run_at(&mut chip8, 0x200);
+        run_at(&mut chip8, 0x300);
+        assert_eq!((chip8.PC, chip8.registers[4]), (0x300, 0));
+
+        let output: std::rc::Rc<std::cell::RefCell<Vec<u8>>> = Default::default();
+        chip8.io_port = Some(Box::new(TestPort {
+            input: vec![0x99],
+            output: output.clone(),
+        }));
+        run_at(&mut chip8, 0x200);
+        assert_eq!(*output.borrow(), vec![0x42]);
+        run_at(&mut chip8, 0x300);
+        assert_eq!((chip8.PC, chip8.registers[4]), (0x302, 0x99));
+        run_at(&mut chip8, 0x300);
+        assert_eq!(chip8.PC, 0x300);
+    }
+
+    #[test]
+    fn ex9e_and_exa1_skip_on_the_low_nibble_of_vx() {
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0xE19E), (0x300, 0xE1A1)]);
+        chip8.registers[1] = 0x1A;
+        run_at(&mut chip8, 0x200);
+        assert_eq!(chip8.PC, 0x202);
+        run_at(&mut chip8, 0x300);
+        assert_eq!(chip8.PC, 0x304);
+
+        chip8.keypad[0xA] = true;
+        run_at(&mut chip8, 0x200);
+        assert_eq!(chip8.PC, 0x204);
+        run_at(&mut chip8, 0x300);
+        assert_eq!(chip8.PC, 0x302);
+    }
+
+    #[test]
+    fn fx07_fx15_and_fx18_move_the_timers() {
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0xF415), (0x202, 0xF518), (0x204, 0xF307)]);
+        chip8.registers[4] = 9;
+        chip8.registers[5] = 4;
+        run_at(&mut chip8, 0x200);
+        chip8.cycle();
+        assert_eq!((chip8.delay_timer, chip8.sound_timer), (9, 4));
+
+        chip8.tick_timers();
+        chip8.cycle();
+        assert_eq!(chip8.registers[3], 8);
+        assert_eq!(chip8.sound_timer, 3);
+    }
+
+    #[test]
+    fn fx1e_adds_vx_to_i_without_touching_vf() {
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0xF21E)]);
+        chip8.registers[2] = 0x12;
+        chip8.IR = 0xFF0;
+        run_at(&mut chip8, 0x200);
+        assert_eq!((chip8.IR, chip8.registers[0xF]), (0x1002, 0));
+    }
+
+    #[test]
+    fn fx29_points_i_at_the_small_digit() {
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0xF629)]);
+        // Only the low nibble counts
+        chip8.registers[6] = 0x3B;
+        run_at(&mut chip8, 0x200);
+        assert_eq!(chip8.IR, 0x50 + 11 * 5);
+        let address: usize = chip8.IR as usize;
+        assert_eq!(chip8.memory[address..address + 5], FONTSET[55..60]);
+    }
+
+    #[test]
+    fn fx30_points_i_at_the_large_digit_or_falls_back_to_the_small_one() {
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0xF630)]);
+        chip8.set_font(Font::builtin("schip").unwrap());
+
+        // The 10 large digits follow the 80 bytes of small characters
+        chip8.registers[6] = 9;
+        run_at(&mut chip8, 0x200);
+        assert_eq!(chip8.IR, 0x50 + 80 + 9 * 10);
+        let address: usize = chip8.IR as usize;
+        assert_eq!(
+            chip8.memory[address..address + 10],
+            chip8.font.large[90..100]
+        );
+
+        // There is no large A
+        chip8.registers[6] = 0xA;
+        run_at(&mut chip8, 0x200);
+        assert_eq!(chip8.IR, 0x50 + 10 * 5);
+    }
+
+    #[test]
+    fn fx33_stores_the_decimal_digits_of_vx() {
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0xF733)]);
+        chip8.IR = 0x300;
+        for (value, digits) in [(255, [2, 5, 5]), (140, [1, 4, 0]), (7, [0, 0, 7])] {
+            chip8.registers[7] = value;
+            run_at(&mut chip8, 0x200);
+            assert_eq!(chip8.memory[0x300..0x303], digits);
+            assert_eq!(chip8.IR, 0x300);
+        }
+    }
+
+    #[test]
+    fn fx55_stores_v0_to_vx_and_leaves_i_past_them() {
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0xF255)]);
+        chip8.registers[..4].copy_from_slice(&[1, 2, 3, 4]);
+        chip8.IR = 0x300;
+        run_at(&mut chip8, 0x200);
+        assert_eq!(chip8.memory[0x300..0x304], [1, 2, 3, 0]);
+        assert_eq!(chip8.IR, 0x303);
+    }
+
+    #[test]
+    fn fx65_loads_v0_to_vx_and_leaves_i_past_them() {
+        let mut chip8: CHIP8 = chip_with(&[(0x200, 0xF265)]);
+        chip8.memory[0x300..0x304].copy_from_slice(&[5, 6, 7, 8]);
+        chip8.IR = 0x300;
+        run_at(&mut chip8, 0x200);
+        assert_eq!(chip8.registers[..4], [5, 6, 7, 0]);
+        assert_eq!(chip8.IR, 0x303);
+
+        // Reads past the end of memory wrap around to the start
+        chip8.memory[0xFFE..].copy_from_slice(&[9, 10]);
+        chip8.memory[0] = 11;
+        chip8.IR = 0xFFE;
+        run_at(&mut chip8, 0x200);
+        assert_eq!(chip8.registers[..3], [9, 10, 11]);
+    }
+}
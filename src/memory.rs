@@ -0,0 +1,170 @@
+use crate::font::FONT_MEMORY_SIZE;
+use crate::{FONTSET_ADDRESS, START_ADDRESS};
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+// 4K Bytes of Memory on a standard machine
+pub const RAM_SIZE: usize = 4096;
+// Largest memory that 16-bit addresses can reach (XO-CHIP)
+pub const MAX_RAM_SIZE: usize = 0x10000;
+
+// The VIP interpreter keeps its work area at the top of memory (0xEA0-0xFFF on a 4K machine)
+// The call stack is pushed downwards from 0xECF, two bytes per entry, so the first entry is 0xECE
+pub const VIP_STACK_OFFSET: usize = 0x132;
+// Bytes from the start of the stack area (0xEA0) to the end of memory
+pub const VIP_WORK_AREA_SIZE: usize = 0x160;
+
+// Layout of a machine's memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryMap {
+    pub load_address: u16, // Address the ROM is loaded at
+    pub ram_size: usize,   // Size of the memory in bytes
+    pub font_address: u16, // Address the built-in font is stored at
+}
+
+impl MemoryMap {
+    // 4K COSMAC VIP running CHIP-8
+    pub const CHIP8: MemoryMap = MemoryMap {
+        load_address: START_ADDRESS,
+        ram_size: RAM_SIZE,
+        font_address: FONTSET_ADDRESS as u16,
+    };
+
+    // Base model 2K COSMAC VIP
+    pub const VIP_2K: MemoryMap = MemoryMap {
+        ram_size: 2048,
+        ..MemoryMap::CHIP8
+    };
+
+    // ETI-660, whose interpreter runs programs from 0x600
+    pub const ETI_660: MemoryMap = MemoryMap {
+        load_address: 0x600,
+        ..MemoryMap::CHIP8
+    };
+
+    // XO-CHIP with the full 64K address space
+    pub const XO_CHIP: MemoryMap = MemoryMap {
+        ram_size: MAX_RAM_SIZE,
+        ..MemoryMap::CHIP8
+    };
+
+    // Check that the program area and the font both fit in memory
+    pub fn validate(&self) -> Result<(), String> {
+        if self.ram_size == 0 || self.ram_size > MAX_RAM_SIZE {
+            return Err(format!(
+                "memory size {} is not between 1 and {} bytes",
+                self.ram_size, MAX_RAM_SIZE
+            ));
+        }
+        if self.load_address as usize >= self.ram_size {
+            return Err(format!(
+                "load address {:#x} is outside of {} bytes of memory",
+                self.load_address, self.ram_size
+            ));
+        }
+        if self.font_address as usize + FONT_MEMORY_SIZE > self.ram_size {
+            return Err(format!(
+                "font at {:#x} does not fit in {} bytes of memory",
+                self.font_address, self.ram_size
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MemoryMap {
+    type Err = String;
+
+    // Look a memory map up by name, or build one from "LOAD:SIZE:FONT" (e.g. "0x600:4096:0x50")
+    fn from_str(spec: &str) -> Result<MemoryMap, String> {
+        let memory_map: MemoryMap = match spec.to_ascii_lowercase().as_str() {
+            "chip8" | "vip" => MemoryMap::CHIP8,
+            "vip2k" => MemoryMap::VIP_2K,
+            "eti660" | "eti-660" => MemoryMap::ETI_660,
+            "xochip" | "xo-chip" => MemoryMap::XO_CHIP,
+            _ => {
+                let fields: Vec<&str> = spec.split(':').collect();
+                if fields.len() != 3 {
+                    return Err(format!("unknown memory map '{}'", spec));
+                }
+                MemoryMap {
+                    load_address: parse_address(fields[0])?,
+                    ram_size: parse_number(fields[1])?,
+                    font_address: parse_address(fields[2])?,
+                }
+            }
+        };
+
+        memory_map.validate()?;
+        Ok(memory_map)
+    }
+}
+
+// Parse a decimal or 0x-prefixed hexadecimal number
+fn parse_number(text: &str) -> Result<usize, String> {
+    let parsed = match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed
+        .ok()
+        .filter(|value| *value <= MAX_RAM_SIZE)
+        .ok_or(format!("'{}' is not a valid address or size", text))
+}
+
+// Parse a number that has to fit in a 16-bit address
+fn parse_address(text: &str) -> Result<u16, String> {
+    u16::try_from(parse_number(text)?)
+        .map_err(|_| format!("'{}' is past the 16-bit address space", text))
+}
+
+// Reasons a ROM can't be loaded
+#[derive(Debug)]
+pub enum RomError {
+    // The file could not be read
+    Io(io::Error),
+    // The configured memory map is unusable
+    MemoryMap(String),
+    // The ROM is bigger than the memory left above the load address
+    TooLarge { size: usize, available: usize },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::Io(error) => write!(f, "could not read ROM: {}", error),
+            RomError::MemoryMap(error) => write!(f, "bad memory map: {}", error),
+            RomError::TooLarge { size, available } => write!(
+                f,
+                "ROM is {} bytes but only {} bytes are free above the load address",
+                size, available
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for RomError {
+    fn from(error: io::Error) -> RomError {
+        RomError::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_past_16_bits_are_rejected() {
+        assert!("0x10000:65536:0x50".parse::<MemoryMap>().is_err());
+        assert!("0x200:65536:0x10000".parse::<MemoryMap>().is_err());
+        assert_eq!(
+            "0xFFF0:65536:0x50".parse::<MemoryMap>(),
+            Ok(MemoryMap {
+                load_address: 0xFFF0,
+                ram_size: MAX_RAM_SIZE,
+                font_address: 0x50,
+            })
+        );
+    }
+}
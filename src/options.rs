@@ -0,0 +1,190 @@
+use crate::pacing::FastForward;
+use crate::terminal::Glyphs;
+use chip8::font::Font;
+use chip8::inline::Protocol;
+use chip8::memory::MemoryMap;
+use chip8::palette::Palette;
+use chip8::phosphor::Persistence;
+use chip8::platform::Platform;
+use chip8::recording::Format;
+use chip8::scale::{self, Filter};
+use chip8::stream::VideoFormat;
+use chip8::timing::TimingModel;
+
+// Settings collected from the command line
+pub struct Options {
+    pub rom_path: String,                 // Path of the ROM file to run
+    pub platform: Option<Platform>, // Interpreter variant, detected from the ROM when not given
+    pub memory_map: Option<MemoryMap>, // Memory layout, the platform's own when not given
+    pub font: Option<Font>,         // Font for Fx29 and Fx30, the default one when not given
+    pub vip_layout: bool,           // Mirror the stack and display into memory like the VIP
+    pub timing: Option<TimingModel>, // Instructions per frame, VIP speed when not given
+    pub display_wait: bool,         // Make Dxyn wait for the vertical blank
+    pub fast_forward: FastForward,  // Speed while the fast-forward key is held
+    pub palette: Option<Palette>,   // Display colours, the default palette when not given
+    pub filters: Vec<Filter>,       // Pixel art filters the display goes through, in order
+    pub screenshot_scale: usize,    // How many times larger screenshots are saved
+    pub record_format: Format,      // Animation format recordings are saved in
+    pub persistence: Option<Persistence>, // Phosphor filter for the display, none when not given
+    pub jit: bool,                  // Compile basic blocks to native code
+    pub jit_verify: Option<u32>,    // Frames to check the JIT against the interpreter for
+    pub headless: Option<u64>,      // Frames to run without a window
+    pub video: Option<VideoFormat>, // Format headless runs write their frames to stdout in
+    pub audio: Option<String>,      // File headless runs write the sound timer's tone to
+    pub inline: Option<Protocol>,   // Protocol headless runs show their last frame with
+    pub terminal: Option<Glyphs>,   // Show the display in the terminal instead of a window
+}
+
+pub const USAGE: &str = "usage: chip8 [--platform chip8|hires|chip8x] \
+[--memory chip8|vip2k|eti660|xochip|LOAD:SIZE:FONT] \
+[--font chip8|vip|dream6800|eti660|schip|FILE] [--vip-layout] \
+[--speed vip|INSTRUCTIONS_PER_FRAME] [--display-wait] \
+[--fast-forward uncapped|TIMES] [--palette amber|green|hp48|high-contrast|octo|BG,FG] \
+[--persistence blend|HALF_LIFE] \
+[--filter scale2x|scale3x|epx|hq2x|scanlines|dotmask[,...]] [--screenshot-scale TIMES] \
+[--record-format gif|apng] [--jit] [--jit-verify FRAMES] \
+[--headless FRAMES [--video y4m|rgba] [--audio FILE] [--inline sixel|kitty]] [--terminal halfblock|braille] <rom>";
+
+impl Options {
+    // Parse the command line arguments (without the program name)
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut rom_path: Option<String> = None;
+        let mut platform: Option<Platform> = None;
+        let mut memory_map: Option<MemoryMap> = None;
+        let mut font: Option<Font> = None;
+        let mut vip_layout: bool = false;
+        let mut timing: Option<TimingModel> = None;
+        let mut display_wait: bool = false;
+        let mut fast_forward: FastForward = FastForward::Uncapped;
+        let mut palette: Option<Palette> = None;
+        let mut persistence: Option<Persistence> = None;
+        let mut filters: Vec<Filter> = Vec::new();
+        let mut screenshot_scale: usize = 1;
+        let mut record_format: Format = Format::Gif;
+        let mut jit: bool = false;
+        let mut jit_verify: Option<u32> = None;
+        let mut headless: Option<u64> = None;
+        let mut video: Option<VideoFormat> = None;
+        let mut audio: Option<String> = None;
+        let mut inline: Option<Protocol> = None;
+        let mut terminal: Option<Glyphs> = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--platform" => {
+                    let name = args.next().ok_or("--platform needs a value")?;
+                    platform = Some(name.parse()?);
+                }
+                "--memory" => {
+                    let spec = args.next().ok_or("--memory needs a value")?;
+                    memory_map = Some(spec.parse()?);
+                }
+                "--font" => {
+                    let name = args.next().ok_or("--font needs a value")?;
+                    font = Some(Font::from_name_or_file(name)?);
+                }
+                "--vip-layout" => vip_layout = true,
+                "--speed" => {
+                    let spec = args.next().ok_or("--speed needs a value")?;
+                    timing = Some(spec.parse()?);
+                }
+                "--display-wait" => display_wait = true,
+                "--fast-forward" => {
+                    let spec = args.next().ok_or("--fast-forward needs a value")?;
+                    fast_forward = spec.parse()?;
+                }
+                "--palette" => {
+                    let spec = args.next().ok_or("--palette needs a value")?;
+                    palette = Some(spec.parse()?);
+                }
+                "--persistence" => {
+                    let spec = args.next().ok_or("--persistence needs a value")?;
+                    persistence = Some(spec.parse()?);
+                }
+                "--filter" => {
+                    let spec = args.next().ok_or("--filter needs a value")?;
+                    filters = scale::parse_filters(spec)?;
+                }
+                "--screenshot-scale" => {
+                    let times = args.next().ok_or("--screenshot-scale needs a value")?;
+                    screenshot_scale = match times.parse() {
+                        Ok(times) if times > 0 => times,
+                        _ => return Err(format!("invalid screenshot scale '{}'", times)),
+                    };
+                }
+                "--record-format" => {
+                    let name = args.next().ok_or("--record-format needs a value")?;
+                    record_format = name.parse()?;
+                }
+                "--jit" => jit = true,
+                "--jit-verify" => {
+                    let frames = args.next().ok_or("--jit-verify needs a value")?;
+                    jit_verify = Some(
+                        frames
+                            .parse()
+                            .map_err(|_| format!("invalid frame count '{}'", frames))?,
+                    );
+                }
+                "--headless" => {
+                    let frames = args.next().ok_or("--headless needs a value")?;
+                    headless = Some(
+                        frames
+                            .parse()
+                            .map_err(|_| format!("invalid frame count '{}'", frames))?,
+                    );
+                }
+                "--video" => {
+                    let name = args.next().ok_or("--video needs a value")?;
+                    video = Some(name.parse()?);
+                }
+                "--audio" => {
+                    let path = args.next().ok_or("--audio needs a value")?;
+                    audio = Some(path.clone());
+                }
+                "--inline" => {
+                    let name = args.next().ok_or("--inline needs a value")?;
+                    inline = Some(name.parse()?);
+                }
+                "--terminal" => {
+                    let name = args.next().ok_or("--terminal needs a value")?;
+                    terminal = Some(name.parse()?);
+                }
+                _ if arg.starts_with("--") => return Err(format!("unknown option '{}'", arg)),
+                _ => rom_path = Some(arg.clone()),
+            }
+        }
+
+        if headless.is_none() && (video.is_some() || audio.is_some() || inline.is_some()) {
+            return Err(String::from(
+                "--video, --audio and --inline need --headless",
+            ));
+        }
+        if video.is_some() && inline.is_some() {
+            return Err(String::from("--video and --inline both write to stdout"));
+        }
+
+        Ok(Options {
+            rom_path: rom_path.ok_or("no ROM file given")?,
+            platform,
+            memory_map,
+            font,
+            vip_layout,
+            timing,
+            display_wait,
+            fast_forward,
+            palette,
+            filters,
+            screenshot_scale,
+            record_format,
+            persistence,
+            jit,
+            jit_verify,
+            headless,
+            video,
+            audio,
+            inline,
+            terminal,
+        })
+    }
+}
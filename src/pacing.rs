@@ -0,0 +1,96 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+// Length of one 60 Hz frame
+pub const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// Frames run back to back when the host falls behind; any further frames are dropped
+pub const MAX_CATCH_UP_FRAMES: u32 = 4;
+
+// How fast fast-forward runs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FastForward {
+    // As many frames as the host can manage
+    Uncapped,
+    // A fixed multiple of normal speed
+    Times(f64),
+}
+
+impl FromStr for FastForward {
+    type Err = String;
+
+    // "uncapped", or a speed multiplier such as "4" or "4x"
+    fn from_str(spec: &str) -> Result<FastForward, String> {
+        if spec.eq_ignore_ascii_case("uncapped") {
+            return Ok(FastForward::Uncapped);
+        }
+        match spec.trim_end_matches(['x', 'X']).parse::<f64>() {
+            Ok(times) if times >= 1.0 => Ok(FastForward::Times(times)),
+            _ => Err(format!("unknown fast-forward speed '{}'", spec)),
+        }
+    }
+}
+
+// Fixed-timestep scheduler that hands out 60 Hz frames as real time passes
+pub struct FramePacer {
+    last_update: Instant,  // When time was last added to the accumulator
+    accumulator: Duration, // Emulated time that hasn't been turned into frames yet
+    dropped_frames: u64,   // Frames skipped because the host fell too far behind
+    speed: f64,            // Emulated time per real time (2.0 runs twice as fast)
+}
+
+impl FramePacer {
+    pub fn new() -> Self {
+        FramePacer {
+            last_update: Instant::now(),
+            accumulator: Duration::ZERO,
+            dropped_frames: 0,
+            speed: 1.0,
+        }
+    }
+
+    // Change how fast emulated time passes compared to real time
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+    }
+
+    // Throw away the time passed since the last call, e.g. while paused
+    pub fn skip(&mut self) {
+        self.last_update = Instant::now();
+        self.accumulator = Duration::ZERO;
+    }
+
+    // Number of frames to run now
+    // At most MAX_CATCH_UP_FRAMES (times the speed) are returned; the time for any frames
+    // beyond that is dropped so a long stall (e.g. dragging the window) doesn't make the game
+    // race afterwards
+    pub fn frames_due(&mut self) -> u32 {
+        let now: Instant = Instant::now();
+        self.accumulator += (now - self.last_update).mul_f64(self.speed);
+        self.last_update = now;
+
+        let max_frames: u32 = MAX_CATCH_UP_FRAMES * self.speed.ceil() as u32;
+        let mut frames: u32 = 0;
+        while self.accumulator >= FRAME_TIME {
+            self.accumulator -= FRAME_TIME;
+            if frames < max_frames {
+                frames += 1;
+            } else {
+                self.dropped_frames += 1;
+            }
+        }
+
+        frames
+    }
+
+    // Real time left until the next frame is due
+    pub fn time_to_next_frame(&self) -> Duration {
+        (FRAME_TIME - self.accumulator)
+            .div_f64(self.speed)
+            .saturating_sub(self.last_update.elapsed())
+    }
+
+    // Frames skipped so far because the host fell behind
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped_frames
+    }
+}
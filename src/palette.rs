@@ -0,0 +1,113 @@
+use std::str::FromStr;
+
+// DISPLAY PALETTES
+// Colours the monochrome display is shown in. XO-CHIP programs draw on two bit planes, so every
+// palette has four colours: neither plane lit, plane 1 lit, plane 2 lit and both lit. Only the
+// first two are used until the second plane is emulated, so palettes given on the command line
+// only have those two. CHIP-8X keeps the colours of its colour board.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [u32; 4], // 0RGB colours, indexed by the planes lit (bit 0 is plane 1)
+}
+
+// Built-in palettes, in the order the palette hotkey cycles through them
+pub const PALETTES: [(&str, Palette); 5] = [
+    // The emulator's original colours
+    (
+        "amber",
+        Palette {
+            colors: [0x000000, 0xFFA500, 0x7F4A00, 0xFFE0A0],
+        },
+    ),
+    // P1 green phosphor, as on most monitors of the time
+    (
+        "green",
+        Palette {
+            colors: [0x001400, 0x33FF66, 0x14802E, 0xB0FFC8],
+        },
+    ),
+    // The HP 48's greyish green LCD
+    (
+        "hp48",
+        Palette {
+            colors: [0x9BA88C, 0x1E2418, 0x5C6650, 0x000000],
+        },
+    ),
+    (
+        "high-contrast",
+        Palette {
+            colors: [0x000000, 0xFFFFFF, 0xFFFF00, 0x00FFFF],
+        },
+    ),
+    // Octo's default colours
+    (
+        "octo",
+        Palette {
+            colors: [0x996600, 0xFFCC00, 0xFF6600, 0x662200],
+        },
+    ),
+];
+
+impl Palette {
+    // Look up a built-in palette by name
+    pub fn builtin(name: &str) -> Option<Palette> {
+        PALETTES
+            .iter()
+            .find(|(builtin, _)| builtin.eq_ignore_ascii_case(name))
+            .map(|(_, palette)| *palette)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        PALETTES[0].1
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    // A built-in palette name, or a background and foreground colour as hex, e.g. "000000,FFFFFF"
+    // The second plane and both planes are shown in the foreground colour
+    fn from_str(spec: &str) -> Result<Palette, String> {
+        if let Some(palette) = Palette::builtin(spec) {
+            return Ok(palette);
+        }
+        if !spec.contains(',') {
+            return Err(format!("unknown palette '{}'", spec));
+        }
+
+        let colors: Vec<u32> = spec
+            .split(',')
+            .map(|color| {
+                let hex: &str = color.trim_start_matches('#');
+                match u32::from_str_radix(hex, 16) {
+                    Ok(color) if hex.len() == 6 => Ok(color),
+                    _ => Err(format!("invalid colour '{}' in palette '{}'", color, spec)),
+                }
+            })
+            .collect::<Result<Vec<u32>, String>>()?;
+        let [background, foreground]: [u32; 2] = colors
+            .try_into()
+            .map_err(|_| format!("palette '{}' needs 2 hex colours", spec))?;
+        Ok(Palette {
+            colors: [background, foreground, foreground, foreground],
+        })
+    }
+}
+
+// Split a 0RGB colour into its channels
+pub fn channels(pixel: u32) -> [f32; 3] {
+    [
+        ((pixel >> 16) & 0xFF) as f32,
+        ((pixel >> 8) & 0xFF) as f32,
+        (pixel & 0xFF) as f32,
+    ]
+}
+
+// Put a 0RGB colour back together from its channels
+pub fn color(channels: [f32; 3]) -> u32 {
+    let [red, green, blue]: [u32; 3] = channels.map(|channel| channel.round() as u32);
+    (red << 16) | (green << 8) | blue
+}
@@ -0,0 +1,180 @@
+use crate::palette::{channels, color};
+use std::str::FromStr;
+
+// PHOSPHOR PERSISTENCE
+// CHIP-8 games erase a sprite and draw it again to move it, so a sprite can be missing from any
+// one frame. A CRT's phosphor kept glowing long enough to hide that; this filter fakes it on the
+// colour image before it is shown.
+
+// How pixels fade out
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Persistence {
+    // Lit pixels lose half their brightness every 'half_life' frames after they go dark
+    Decay(f64),
+    // Every frame is shown mixed half and half with the one before it
+    Blend,
+}
+
+impl FromStr for Persistence {
+    type Err = String;
+
+    // "blend", or a half-life in frames
+    fn from_str(spec: &str) -> Result<Persistence, String> {
+        if spec.eq_ignore_ascii_case("blend") {
+            return Ok(Persistence::Blend);
+        }
+        match spec.parse() {
+            Ok(half_life) if half_life > 0.0 => Ok(Persistence::Decay(half_life)),
+            _ => Err(format!("unknown persistence '{}'", spec)),
+        }
+    }
+}
+
+// Filter between the colour image of the display and the screen
+pub struct Phosphor {
+    persistence: Persistence,
+    glow: Vec<f32>,     // Brightness of every colour channel of every pixel on screen
+    previous: Vec<u32>, // Last image given to the filter, for blending
+    fading: bool,       // Some pixels are still brighter than the image under them
+}
+
+impl Phosphor {
+    pub fn new(persistence: Persistence) -> Phosphor {
+        Phosphor {
+            persistence,
+            glow: Vec::new(),
+            previous: Vec::new(),
+            fading: false,
+        }
+    }
+
+    // Apply the filter to an image in place, 'frames' frames after the last one
+    pub fn apply(&mut self, image: &mut [u32], frames: u32) {
+        // Start again from scratch whenever the image size changes
+        if self.previous.len() != image.len() {
+            self.glow = image.iter().flat_map(|pixel| channels(*pixel)).collect();
+            self.previous = image.to_vec();
+            self.fading = false;
+            return;
+        }
+
+        self.fading = false;
+        match self.persistence {
+            Persistence::Decay(half_life) => {
+                let decay: f32 = 0.5_f64.powf(frames as f64 / half_life) as f32;
+                for (pixel, glow) in image.iter_mut().zip(self.glow.chunks_mut(3)) {
+                    let target: [f32; 3] = channels(*pixel);
+                    for (glow, target) in glow.iter_mut().zip(target) {
+                        *glow = (*glow * decay).max(target);
+                        // Snap to the image once the difference can't be seen
+                        if *glow - target < 1.0 {
+                            *glow = target;
+                        } else {
+                            self.fading = true;
+                        }
+                    }
+                    *pixel = color([glow[0], glow[1], glow[2]]);
+                }
+            }
+            Persistence::Blend => {
+                for (pixel, previous) in image.iter_mut().zip(self.previous.iter_mut()) {
+                    let current: u32 = *pixel;
+                    if frames > 0 && current != *previous {
+                        let (a, b): ([f32; 3], [f32; 3]) = (channels(current), channels(*previous));
+                        *pixel = color([
+                            (a[0] + b[0]) / 2.0,
+                            (a[1] + b[1]) / 2.0,
+                            (a[2] + b[2]) / 2.0,
+                        ]);
+                        self.fading = true;
+                    }
+                    *previous = current;
+                }
+            }
+        }
+    }
+
+    // Whether the last filtered image still differs from the image it was made from, so the
+    // screen needs updating again even if the display doesn't change
+    pub fn fading(&self) -> bool {
+        self.fading
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: u32 = 0x000000;
+    const WHITE: u32 = 0xFFFFFF;
+
+    #[test]
+    fn decay_halves_every_half_life_until_it_cant_be_seen() {
+        let mut phosphor: Phosphor = Phosphor::new(Persistence::Decay(1.0));
+        let mut image: [u32; 2] = [WHITE, BLACK];
+        phosphor.apply(&mut image, 1);
+        assert_eq!(image, [WHITE, BLACK]);
+
+        // 255 / 2 = 127.5
+        let mut image: [u32; 2] = [BLACK, BLACK];
+        phosphor.apply(&mut image, 1);
+        assert_eq!((image, phosphor.fading()), ([0x808080, BLACK], true));
+
+        // 127.5 / 4 = 31.875
+        let mut image: [u32; 2] = [BLACK, BLACK];
+        phosphor.apply(&mut image, 2);
+        assert_eq!((image, phosphor.fading()), ([0x202020, BLACK], true));
+
+        // 31.875 / 256 is under 1, so the pixel is dark again
+        let mut image: [u32; 2] = [BLACK, BLACK];
+        phosphor.apply(&mut image, 8);
+        assert_eq!((image, phosphor.fading()), ([BLACK, BLACK], false));
+    }
+
+    #[test]
+    fn decay_lights_pixels_up_at_once() {
+        let mut phosphor: Phosphor = Phosphor::new(Persistence::Decay(4.0));
+        phosphor.apply(&mut [0x400000], 1);
+        let mut image: [u32; 1] = [0x00FF00];
+        phosphor.apply(&mut image, 4);
+        // Red fades to half while green is at full brightness straight away
+        assert_eq!((image, phosphor.fading()), ([0x20FF00], true));
+    }
+
+    #[test]
+    fn blend_mixes_changed_pixels_with_the_last_image() {
+        let mut phosphor: Phosphor = Phosphor::new(Persistence::Blend);
+        phosphor.apply(&mut [WHITE, 0x0000FF], 1);
+
+        let mut image: [u32; 2] = [BLACK, 0x0000FF];
+        phosphor.apply(&mut image, 1);
+        assert_eq!((image, phosphor.fading()), ([0x808080, 0x0000FF], true));
+
+        // The last image was black, not the blend shown
+        let mut image: [u32; 2] = [BLACK, 0x0000FF];
+        phosphor.apply(&mut image, 1);
+        assert_eq!((image, phosphor.fading()), ([BLACK, 0x0000FF], false));
+
+        // Nothing is blended when no frame has gone by
+        let mut image: [u32; 2] = [WHITE, 0x0000FF];
+        phosphor.apply(&mut image, 0);
+        assert_eq!(image, [WHITE, 0x0000FF]);
+    }
+
+    #[test]
+    fn a_new_image_size_starts_again() {
+        let mut phosphor: Phosphor = Phosphor::new(Persistence::Decay(1.0));
+        phosphor.apply(&mut [WHITE], 1);
+        let mut image: [u32; 2] = [BLACK, BLACK];
+        phosphor.apply(&mut image, 1);
+        assert_eq!((image, phosphor.fading()), ([BLACK, BLACK], false));
+    }
+
+    #[test]
+    fn persistence_is_blend_or_a_positive_half_life() {
+        assert_eq!("Blend".parse(), Ok(Persistence::Blend));
+        assert_eq!("2.5".parse(), Ok(Persistence::Decay(2.5)));
+        assert!("0".parse::<Persistence>().is_err());
+        assert!("slow".parse::<Persistence>().is_err());
+    }
+}
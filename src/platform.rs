@@ -0,0 +1,93 @@
+use crate::memory::MemoryMap;
+use std::str::FromStr;
+
+// Hi-res programs begin with a jump into the patched interpreter at 0x260
+const HIRES_SIGNATURE: [u8; 2] = [0x12, 0x60];
+// The CHIP-8 part of a hi-res program begins after the interpreter patch (0x2C0)
+const HIRES_ENTRY_OFFSET: u16 = 0xC0;
+// CHIP-8X programs are loaded above the larger CHIP-8X interpreter
+const CHIP8X_START_ADDRESS: u16 = 0x300;
+// File name extensions CHIP-8X programs are kept under
+const CHIP8X_EXTENSIONS: [&str; 2] = [".c8x", ".ch8x"];
+
+// The CHIP-8 interpreter variants that can be emulated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Platform {
+    // Original COSMAC VIP CHIP-8 with a 64 by 32 display
+    Chip8,
+    // Two-page hi-res CHIP-8 with a 64 by 64 display
+    HiRes,
+    // CHIP-8X with the VP-590 colour board and VP-580 second keypad
+    Chip8X,
+}
+
+impl Platform {
+    // Guess the variant a ROM was written for by looking at its first instruction
+    // CHIP-8X programs can't be told apart from CHIP-8 ones and have to be selected by hand
+    pub fn detect(rom: &[u8]) -> Platform {
+        if rom.starts_with(&HIRES_SIGNATURE) {
+            Platform::HiRes
+        } else {
+            Platform::Chip8
+        }
+    }
+
+    // The variant a ROM file is for, when its file name says so
+    // Frontends that don't get the platform from the user pick CHIP-8X programs out this way
+    pub fn from_file_name(name: &str) -> Option<Platform> {
+        let name: String = name.to_ascii_lowercase();
+        CHIP8X_EXTENSIONS
+            .iter()
+            .any(|extension| name.ends_with(extension))
+            .then_some(Platform::Chip8X)
+    }
+
+    // Width of the display in pixels
+    pub fn width(self) -> usize {
+        64
+    }
+
+    // Height of the display in pixels
+    // The hi-res interpreter uses two 256 byte display pages stacked on top of each other
+    pub fn height(self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::Chip8X => 32,
+            Platform::HiRes => 64,
+        }
+    }
+
+    // Memory layout used by the platform unless another one is configured
+    pub fn memory_map(self) -> MemoryMap {
+        match self {
+            Platform::Chip8 | Platform::HiRes => MemoryMap::CHIP8,
+            Platform::Chip8X => MemoryMap {
+                load_address: CHIP8X_START_ADDRESS,
+                ..MemoryMap::CHIP8
+            },
+        }
+    }
+
+    // Address of the first CHIP-8 instruction to execute, given where the ROM was loaded
+    // A hi-res ROM carries the 1802 machine code patch for the interpreter between 0x260
+    // and 0x2BF, so the `1260` jump is skipped and execution starts directly at 0x2C0
+    pub fn entry_point(self, load_address: u16) -> u16 {
+        match self {
+            Platform::Chip8 | Platform::Chip8X => load_address,
+            Platform::HiRes => load_address.wrapping_add(HIRES_ENTRY_OFFSET),
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    // Look a platform up by the name used on the command line
+    fn from_str(name: &str) -> Result<Platform, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "chip8" | "chip-8" => Ok(Platform::Chip8),
+            "hires" => Ok(Platform::HiRes),
+            "chip8x" | "chip-8x" => Ok(Platform::Chip8X),
+            _ => Err(format!("unknown platform '{}'", name)),
+        }
+    }
+}
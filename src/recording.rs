@@ -0,0 +1,387 @@
+use crate::scale::Image;
+use std::env;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// RECORDING
+// Frames are written out as they are recorded. Only frames that differ from the one before are
+// kept; each one is shown until the frame number of the next, so the animation plays back at the
+// emulated 60 Hz. That means an image can only be written once the next one comes along, so the
+// recorder holds on to the last image and nothing else. GIF frames go straight into the file.
+// An APNG has to say how many frames it has before the first one, so its frames wait in a
+// temporary file until the recording stops.
+
+const FRAME_RATE: u64 = 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Gif,
+    Apng,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Format, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "gif" => Ok(Format::Gif),
+            "apng" | "png" => Ok(Format::Apng),
+            _ => Err(format!("unknown recording format '{}'", name)),
+        }
+    }
+}
+
+impl Format {
+    // File name extension for the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Gif => "gif",
+            Format::Apng => "png",
+        }
+    }
+}
+
+pub struct Recorder {
+    format: Format,
+    path: PathBuf,
+    output: Option<Output>, // Opened with the first image, once its size is known
+    pending: Option<(u64, Image)>, // Last image and the frame number it was first shown at
+}
+
+enum Output {
+    Gif(gif::Encoder<BufWriter<File>>),
+    Apng(Spool),
+}
+
+impl Recorder {
+    pub fn new(format: Format, path: PathBuf) -> Recorder {
+        Recorder {
+            format,
+            path,
+            output: None,
+            pending: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Add the image shown from frame number 'frame' on
+    // Images the same as the last one are skipped, as are images of another size than the first
+    pub fn capture(
+        &mut self,
+        pixels: &[u32],
+        width: usize,
+        height: usize,
+        frame: u64,
+    ) -> Result<(), String> {
+        if let Some((_, last)) = &self.pending
+            && (last.pixels == pixels || last.width != width || last.height != height)
+        {
+            return Ok(());
+        }
+
+        let image: Image = Image::new(width, height, pixels.to_vec());
+        self.pending = match (self.pending.take(), self.output.as_mut()) {
+            (Some((start, previous)), Some(output)) => {
+                if output
+                    .add(&previous, start, frame)
+                    .map_err(|error| self.fail(&error))?
+                {
+                    Some((frame, image))
+                } else {
+                    // Shown too briefly to keep; the new image takes over its start
+                    Some((start, image))
+                }
+            }
+            _ => {
+                self.output = Some(
+                    self.open(width, height)
+                        .map_err(|error| self.fail(&error))?,
+                );
+                Some((frame, image))
+            }
+        };
+        Ok(())
+    }
+
+    // Write the animation, with the last image shown until frame number 'end'
+    pub fn finish(mut self, end: u64) -> Result<(), String> {
+        let (Some((start, image)), Some(output)) = (self.pending.take(), self.output.take()) else {
+            return Err(String::from("nothing was recorded"));
+        };
+        output
+            .finish(&image, start, end, &self.path)
+            .map_err(|error| self.fail(&error))
+    }
+
+    // Start the output once the image size is known
+    // Like screenshots, recordings never write over a file that is already there
+    fn open(&self, width: usize, height: usize) -> io::Result<Output> {
+        match self.format {
+            Format::Gif => {
+                let file: BufWriter<File> = BufWriter::new(
+                    File::options()
+                        .write(true)
+                        .create_new(true)
+                        .open(&self.path)?,
+                );
+                let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &[])
+                    .map_err(io::Error::other)?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(io::Error::other)?;
+                Ok(Output::Gif(encoder))
+            }
+            Format::Apng => Ok(Output::Apng(Spool::new(&self.path, width, height)?)),
+        }
+    }
+
+    fn fail(&self, error: &dyn Display) -> String {
+        format!("could not save '{}': {}", self.path.display(), error)
+    }
+}
+
+// GIF delays are in hundredths of a second, and most viewers slow down delays under two of them,
+// so an image that would be shown for less than that is dropped in favour of the next one
+fn centiseconds(frame: u64) -> u64 {
+    (frame * 100 + FRAME_RATE / 2) / FRAME_RATE
+}
+
+impl Output {
+    // Write an image shown from frame number 'start' until 'end'
+    // Returns false if the image was too short-lived to write
+    fn add(&mut self, image: &Image, start: u64, end: u64) -> io::Result<bool> {
+        match self {
+            Output::Gif(encoder) => {
+                let (start, end): (u64, u64) = (centiseconds(start), centiseconds(end));
+                if end < start + 2 {
+                    return Ok(false);
+                }
+                write_gif_frame(encoder, image, end - start).map_err(io::Error::other)?;
+            }
+            // APNG delays are fractions of a second, so every image gets its exact number of
+            // 60 Hz frames
+            Output::Apng(spool) => spool.add(image, (end - start).max(1))?,
+        }
+        Ok(true)
+    }
+
+    // Write the last image and close the file
+    fn finish(self, image: &Image, start: u64, end: u64, path: &Path) -> io::Result<()> {
+        match self {
+            Output::Gif(mut encoder) => {
+                let delay: u64 = centiseconds(end).saturating_sub(centiseconds(start));
+                write_gif_frame(&mut encoder, image, delay.max(2)).map_err(io::Error::other)?;
+                encoder.into_inner()?.flush()
+            }
+            Output::Apng(mut spool) => {
+                spool.add(image, (end - start).max(1))?;
+                spool.write_apng(path)
+            }
+        }
+    }
+}
+
+fn write_gif_frame(
+    encoder: &mut gif::Encoder<BufWriter<File>>,
+    image: &Image,
+    delay: u64,
+) -> Result<(), gif::EncodingError> {
+    let mut frame = gif::Frame::from_rgb_speed(
+        image.width as u16,
+        image.height as u16,
+        &image.rgb_bytes(),
+        10,
+    );
+    frame.delay = delay.min(u16::MAX as u64) as u16;
+    encoder.write_frame(&frame)
+}
+
+// Temporary file holding the frames of an APNG until they are counted
+// Every frame is its delay in 60 Hz frames (u16, little-endian) followed by its RGB bytes
+struct Spool {
+    path: PathBuf,
+    file: BufWriter<File>,
+    width: usize,
+    height: usize,
+    frames: u32,
+}
+
+impl Spool {
+    // Create the temporary file, named after the recording it is for
+    fn new(recording: &Path, width: usize, height: usize) -> io::Result<Spool> {
+        let name: String = recording
+            .file_name()
+            .map_or(String::from("recording"), |name| {
+                name.to_string_lossy().into_owned()
+            });
+        let path: PathBuf = env::temp_dir().join(format!("{}.{}.frames", name, std::process::id()));
+        let file: File = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        Ok(Spool {
+            path,
+            file: BufWriter::new(file),
+            width,
+            height,
+            frames: 0,
+        })
+    }
+
+    fn add(&mut self, image: &Image, delay: u64) -> io::Result<()> {
+        let delay: u16 = delay.min(u16::MAX as u64) as u16;
+        self.file.write_all(&delay.to_le_bytes())?;
+        self.file.write_all(&image.rgb_bytes())?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    // Read the frames back and encode them into an APNG at 'path'
+    fn write_apng(&mut self, path: &Path) -> io::Result<()> {
+        self.file.flush()?;
+        let spooled: &mut File = self.file.get_mut();
+        spooled.seek(SeekFrom::Start(0))?;
+        let mut reader: BufReader<&mut File> = BufReader::new(spooled);
+
+        let file: BufWriter<File> =
+            BufWriter::new(File::options().write(true).create_new(true).open(path)?);
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(self.frames, 0)?;
+
+        let mut writer = encoder.write_header()?;
+        let mut delay: [u8; 2] = [0; 2];
+        let mut rgb: Vec<u8> = vec![0; self.width * self.height * 3];
+        for _ in 0..self.frames {
+            reader.read_exact(&mut delay)?;
+            reader.read_exact(&mut rgb)?;
+            writer.set_frame_delay(u16::from_le_bytes(delay), FRAME_RATE as u16)?;
+            writer.write_image_data(&rgb)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: [u32; 2] = [0xFF0000, 0x000000];
+    const B: [u32; 2] = [0x00FF00, 0x000000];
+    const C: [u32; 2] = [0x0000FF, 0xFFFFFF];
+
+    // Path in the temporary directory that nothing else uses, with nothing there yet
+    fn scratch_path(name: &str) -> PathBuf {
+        let path: PathBuf = env::temp_dir().join(format!("{}.{}", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    // A from frame 0, B from frame 10, B again (skipped), C from frame 11, and an image of
+    // another size (skipped), until frame 70
+    fn record(format: Format, path: &Path) {
+        let mut recorder: Recorder = Recorder::new(format, path.to_path_buf());
+        recorder.capture(&A, 2, 1, 0).unwrap();
+        recorder.capture(&B, 2, 1, 10).unwrap();
+        recorder.capture(&B, 2, 1, 11).unwrap();
+        recorder.capture(&C, 2, 1, 11).unwrap();
+        recorder.capture(&C, 1, 2, 12).unwrap();
+        recorder.finish(70).unwrap();
+    }
+
+    #[test]
+    fn frames_are_rounded_to_hundredths() {
+        let delays: Vec<u64> = [0, 1, 2, 3, 10, 60].map(centiseconds).to_vec();
+        assert_eq!(delays, vec![0, 2, 3, 5, 17, 100]);
+    }
+
+    #[test]
+    fn gif_merges_images_too_short_for_a_delay() {
+        let path: PathBuf = scratch_path("merge.gif");
+        record(Format::Gif, &path);
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(File::open(&path).unwrap()).unwrap();
+        let mut frames: Vec<(u16, Vec<u8>)> = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push((frame.delay, frame.buffer.to_vec()));
+        }
+        fs::remove_file(&path).unwrap();
+
+        // B was on for under 2 hundredths, so C takes its place from frame 10
+        assert_eq!(
+            frames,
+            vec![
+                (17, vec![255, 0, 0, 255, 0, 0, 0, 255]),
+                (100, vec![0, 0, 255, 255, 255, 255, 255, 255]),
+            ]
+        );
+    }
+
+    #[test]
+    fn apng_keeps_every_image_for_its_frames() {
+        let path: PathBuf = scratch_path("merge.png");
+        record(Format::Apng, &path);
+
+        let mut reader = png::Decoder::new(File::open(&path).unwrap())
+            .read_info()
+            .unwrap();
+        let frame_count: u32 = reader.info().animation_control.unwrap().num_frames;
+        let mut frames: Vec<(u16, u16, Vec<u8>)> = Vec::new();
+        let mut rgb: Vec<u8> = vec![0; reader.output_buffer_size()];
+        for _ in 0..frame_count {
+            reader.next_frame(&mut rgb).unwrap();
+            let control: png::FrameControl = reader.info().frame_control.unwrap();
+            frames.push((control.delay_num, control.delay_den, rgb.clone()));
+        }
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            frames,
+            vec![
+                (10, 60, vec![255, 0, 0, 0, 0, 0]),
+                (1, 60, vec![0, 255, 0, 0, 0, 0]),
+                (59, 60, vec![0, 0, 255, 255, 255, 255]),
+            ]
+        );
+    }
+
+    #[test]
+    fn recordings_never_write_over_a_file() {
+        let path: PathBuf = scratch_path("taken.gif");
+        fs::write(&path, b"taken").unwrap();
+        let mut recorder: Recorder = Recorder::new(Format::Gif, path.clone());
+        let result: Result<(), String> = recorder.capture(&A, 2, 1, 0);
+        let contents: Vec<u8> = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(contents, b"taken");
+    }
+
+    #[test]
+    fn an_empty_recording_is_an_error() {
+        let recorder: Recorder = Recorder::new(Format::Gif, scratch_path("empty.gif"));
+        assert_eq!(
+            recorder.finish(10),
+            Err(String::from("nothing was recorded"))
+        );
+    }
+}
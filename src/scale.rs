@@ -0,0 +1,504 @@
+use crate::palette::{channels, color};
+use std::str::FromStr;
+
+// PIXEL ART FILTERS
+// Filters that scale the colour image of the display up on the CPU, so the result looks the same
+// in the window, in screenshots and in recordings. Every filter makes the image a whole number
+// of times larger, and filters can be chained.
+
+// A colour image, one 0RGB u32 per pixel, row by row
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, pixels: Vec<u32>) -> Image {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "image size doesn't match its pixels"
+        );
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    // The pixels as RGB bytes, e.g. for the rows of a PNG
+    pub fn rgb_bytes(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|pixel| {
+                let [_, red, green, blue]: [u8; 4] = pixel.to_be_bytes();
+                [red, green, blue]
+            })
+            .collect()
+    }
+
+    // Make the image 'factor' times larger with square pixels
+    pub fn enlarge(&self, factor: usize) -> Image {
+        if factor <= 1 {
+            return self.clone();
+        }
+        self.scaled(factor, |x, y| {
+            vec![self.pixels[y * self.width + x]; factor * factor]
+        })
+    }
+
+    // Pixel at (x, y), where positions off the edge repeat the nearest edge pixel
+    fn get(&self, x: isize, y: isize) -> u32 {
+        let x: usize = x.clamp(0, self.width as isize - 1) as usize;
+        let y: usize = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+
+    // The 3 by 3 block of pixels around (x, y), row by row
+    fn neighbours(&self, x: usize, y: usize) -> [u32; 9] {
+        let (x, y): (isize, isize) = (x as isize, y as isize);
+        [
+            self.get(x - 1, y - 1),
+            self.get(x, y - 1),
+            self.get(x + 1, y - 1),
+            self.get(x - 1, y),
+            self.get(x, y),
+            self.get(x + 1, y),
+            self.get(x - 1, y + 1),
+            self.get(x, y + 1),
+            self.get(x + 1, y + 1),
+        ]
+    }
+
+    // Build an image 'factor' times larger, where every source pixel is replaced by the
+    // 'factor' by 'factor' block 'block' returns for it, row by row
+    fn scaled(&self, factor: usize, block: impl Fn(usize, usize) -> Vec<u32>) -> Image {
+        let width: usize = self.width * factor;
+        let mut pixels: Vec<u32> = vec![0; width * self.height * factor];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                for (i, pixel) in block(x, y).into_iter().enumerate() {
+                    pixels[(y * factor + i / factor) * width + x * factor + i % factor] = pixel;
+                }
+            }
+        }
+        Image::new(width, self.height * factor, pixels)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    // AdvanceMAME's edge-following doubler
+    Scale2x,
+    // The same idea at three times the size
+    Scale3x,
+    // Eric's Pixel Expansion, the original form of Scale2x
+    Epx,
+    // Maxim Stepin's hq2x, which picks how to interpolate by which neighbours look alike
+    Hq2x,
+    // Doubles the image and darkens every second line, like the gaps between a CRT's scan lines
+    Scanlines,
+    // Triples the image and splits every pixel into red, green and blue stripes, like an
+    // aperture grille
+    DotMask,
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Filter, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "scale2x" => Ok(Filter::Scale2x),
+            "scale3x" => Ok(Filter::Scale3x),
+            "epx" => Ok(Filter::Epx),
+            "hq2x" => Ok(Filter::Hq2x),
+            "scanlines" => Ok(Filter::Scanlines),
+            "dotmask" | "dot-mask" => Ok(Filter::DotMask),
+            _ => Err(format!("unknown filter '{}'", name)),
+        }
+    }
+}
+
+impl Filter {
+    // How many times larger the filter makes the image
+    pub fn factor(&self) -> usize {
+        match self {
+            Filter::Scale3x | Filter::DotMask => 3,
+            _ => 2,
+        }
+    }
+
+    pub fn apply(&self, image: &Image) -> Image {
+        match self {
+            Filter::Scale2x => image.scaled(2, |x, y| scale2x(image.neighbours(x, y)).to_vec()),
+            Filter::Scale3x => image.scaled(3, |x, y| scale3x(image.neighbours(x, y)).to_vec()),
+            Filter::Epx => image.scaled(2, |x, y| epx(image.neighbours(x, y)).to_vec()),
+            Filter::Hq2x => image.scaled(2, |x, y| hq2x(image.neighbours(x, y)).to_vec()),
+            Filter::Scanlines => image.scaled(2, |x, y| {
+                let pixel: u32 = image.pixels[y * image.width + x];
+                let dark: u32 = scale_color(pixel, [0.5, 0.5, 0.5]);
+                vec![pixel, pixel, dark, dark]
+            }),
+            Filter::DotMask => image.scaled(3, |x, y| {
+                let pixel: u32 = image.pixels[y * image.width + x];
+                let stripes: [u32; 3] = [
+                    scale_color(pixel, [1.0, 0.4, 0.4]),
+                    scale_color(pixel, [0.4, 1.0, 0.4]),
+                    scale_color(pixel, [0.4, 0.4, 1.0]),
+                ];
+                stripes.repeat(3)
+            }),
+        }
+    }
+}
+
+// Parse a comma separated list of filters, applied in order
+pub fn parse_filters(spec: &str) -> Result<Vec<Filter>, String> {
+    spec.split(',').map(str::parse).collect()
+}
+
+// How many times larger a chain of filters makes the image
+pub fn factor(filters: &[Filter]) -> usize {
+    filters.iter().map(Filter::factor).product()
+}
+
+// Run an image through a chain of filters
+pub fn apply(filters: &[Filter], image: Image) -> Image {
+    filters
+        .iter()
+        .fold(image, |image, filter| filter.apply(&image))
+}
+
+// In the filters below the 3 by 3 neighbourhood is named
+//   A B C
+//   D E F
+//   G H I
+// and the output block is returned row by row.
+
+fn scale2x([_, b, _, d, e, f, _, h, _]: [u32; 9]) -> [u32; 4] {
+    if b != h && d != f {
+        [
+            if d == b { d } else { e },
+            if b == f { f } else { e },
+            if d == h { d } else { e },
+            if h == f { f } else { e },
+        ]
+    } else {
+        [e; 4]
+    }
+}
+
+fn scale3x([a, b, c, d, e, f, g, h, i]: [u32; 9]) -> [u32; 9] {
+    if b == h || d == f {
+        return [e; 9];
+    }
+    [
+        if d == b { d } else { e },
+        if (d == b && e != c) || (b == f && e != a) {
+            b
+        } else {
+            e
+        },
+        if b == f { f } else { e },
+        if (d == b && e != g) || (d == h && e != a) {
+            d
+        } else {
+            e
+        },
+        e,
+        if (b == f && e != i) || (h == f && e != c) {
+            f
+        } else {
+            e
+        },
+        if d == h { d } else { e },
+        if (d == h && e != i) || (h == f && e != g) {
+            h
+        } else {
+            e
+        },
+        if h == f { f } else { e },
+    ]
+}
+
+// EPX names the centre P and its neighbours A (up), B (right), C (left) and D (down)
+fn epx([_, up, _, left, p, right, _, down, _]: [u32; 9]) -> [u32; 4] {
+    let (a, b, c, d): (u32, u32, u32, u32) = (up, right, left, down);
+    let mut block: [u32; 4] = [p; 4];
+    if c == a {
+        block[0] = a;
+    }
+    if a == b {
+        block[1] = b;
+    }
+    if d == c {
+        block[2] = c;
+    }
+    if b == d {
+        block[3] = d;
+    }
+    // Three or more equal neighbours would fill in the centre, so leave it alone
+    // (three equal colours make three equal pairs, two different pairs only two)
+    let equal_pairs: usize = [a == b, a == c, a == d, b == c, b == d, c == d]
+        .iter()
+        .filter(|equal| **equal)
+        .count();
+    if equal_pairs >= 3 { [p; 4] } else { block }
+}
+
+// hq2x
+// Every neighbour is marked as looking alike or different from the centre, and the 256 ways that
+// can come out pick how each quarter of the pixel is interpolated. The table is for the top-left
+// quarter; the other quarters turn the neighbourhood round until their corner is at the top left.
+fn hq2x(neighbours: [u32; 9]) -> [u32; 4] {
+    let top_left: [u32; 9] = neighbours;
+    let top_right: [u32; 9] = turn(top_left);
+    let bottom_right: [u32; 9] = turn(top_right);
+    let bottom_left: [u32; 9] = turn(bottom_right);
+    [
+        hq2x_corner(top_left),
+        hq2x_corner(top_right),
+        hq2x_corner(bottom_left),
+        hq2x_corner(bottom_right),
+    ]
+}
+
+// Turn a neighbourhood a quarter turn anticlockwise, so its top-right corner is at the top left
+fn turn([a, b, c, d, e, f, g, h, i]: [u32; 9]) -> [u32; 9] {
+    [c, f, i, b, e, h, a, d, g]
+}
+
+// The top-left quarter of the centre pixel
+fn hq2x_corner(neighbours: [u32; 9]) -> u32 {
+    let [a, b, _, d, e, f, _, h, _]: [u32; 9] = neighbours;
+    // Bit 0 for A through to bit 7 for I, skipping the centre
+    let pattern: usize = [0, 1, 2, 3, 5, 6, 7, 8]
+        .iter()
+        .enumerate()
+        .filter(|(_, neighbour)| !similar(e, neighbours[**neighbour]))
+        .fold(0, |pattern, (bit, _)| pattern | 1 << bit);
+
+    // Some patterns also depend on whether the edge neighbours look alike
+    let blend: Blend = match HQ2X_PATTERNS[pattern] {
+        Corner::Always(blend) => blend,
+        Corner::IfEdge(differ, alike) => {
+            if similar(d, b) {
+                alike
+            } else {
+                differ
+            }
+        }
+        Corner::IfNextEdge(differ, alike) => {
+            if similar(b, f) {
+                alike
+            } else {
+                differ
+            }
+        }
+        Corner::IfPreviousEdge(differ, alike) => {
+            if similar(h, d) {
+                alike
+            } else {
+                differ
+            }
+        }
+    };
+    match blend {
+        Blend::B0 => e,
+        Blend::B10 => mix(&[(e, 3), (a, 1)]),
+        Blend::B11 => mix(&[(e, 3), (d, 1)]),
+        Blend::B12 => mix(&[(e, 3), (b, 1)]),
+        Blend::B20 => mix(&[(e, 2), (d, 1), (b, 1)]),
+        Blend::B21 => mix(&[(e, 2), (a, 1), (b, 1)]),
+        Blend::B22 => mix(&[(e, 2), (a, 1), (d, 1)]),
+        Blend::B60 => mix(&[(e, 5), (b, 2), (d, 1)]),
+        Blend::B61 => mix(&[(e, 5), (d, 2), (b, 1)]),
+        Blend::B70 => mix(&[(e, 6), (d, 1), (b, 1)]),
+        Blend::B90 => mix(&[(e, 2), (d, 3), (b, 3)]),
+        Blend::B100 => mix(&[(e, 14), (d, 1), (b, 1)]),
+    }
+}
+
+// The interpolations hq2x makes the top-left quarter with, numbered as in the original
+#[derive(Clone, Copy)]
+enum Blend {
+    B0,
+    B10,
+    B11,
+    B12,
+    B20,
+    B21,
+    B22,
+    B60,
+    B61,
+    B70,
+    B90,
+    B100,
+}
+
+// How hq2x picks the interpolation for one pattern
+// Where it depends on an edge, the first interpolation is for neighbours that differ
+#[derive(Clone, Copy)]
+enum Corner {
+    Always(Blend),
+    // Depends on the edge between B and D, the one the quarter sits on
+    IfEdge(Blend, Blend),
+    // Depends on the edge between B and F, the top-right quarter's
+    IfNextEdge(Blend, Blend),
+    // Depends on the edge between D and H, the bottom-left quarter's
+    IfPreviousEdge(Blend, Blend),
+}
+
+const C10: Corner = Corner::Always(Blend::B10);
+const C11: Corner = Corner::Always(Blend::B11);
+const C12: Corner = Corner::Always(Blend::B12);
+const C20: Corner = Corner::Always(Blend::B20);
+const C21: Corner = Corner::Always(Blend::B21);
+const C22: Corner = Corner::Always(Blend::B22);
+const E0_20: Corner = Corner::IfEdge(Blend::B0, Blend::B20);
+const E0_90: Corner = Corner::IfEdge(Blend::B0, Blend::B90);
+const E0_100: Corner = Corner::IfEdge(Blend::B0, Blend::B100);
+const E10_20: Corner = Corner::IfEdge(Blend::B10, Blend::B20);
+const E10_70: Corner = Corner::IfEdge(Blend::B10, Blend::B70);
+const E10_90: Corner = Corner::IfEdge(Blend::B10, Blend::B90);
+const N11_60: Corner = Corner::IfNextEdge(Blend::B11, Blend::B60);
+const P12_61: Corner = Corner::IfPreviousEdge(Blend::B12, Blend::B61);
+
+// hq2x's top-left quarter for every pattern of neighbours that differ from the centre
+const HQ2X_PATTERNS: [Corner; 256] = [
+    C20, C20, C22, C11, C20, C20, C22, C11, // 0x00-0x07
+    C21, C12, E10_20, E0_20, C21, C12, E10_90, E0_90, // 0x08-0x0F
+    C20, C20, C22, N11_60, C20, C20, C22, N11_60, // 0x10-0x17
+    C21, C12, E0_20, E0_20, C21, C12, C10, E0_20, // 0x18-0x1F
+    C20, C20, C22, C11, C20, C20, C22, C11, // 0x20-0x27
+    C21, C12, E10_90, E0_90, C21, C12, E10_70, E0_100, // 0x28-0x2F
+    C20, C20, C22, N11_60, C20, C20, C22, N11_60, // 0x30-0x37
+    C21, C12, E10_70, E0_20, C21, C12, C10, E0_100, // 0x38-0x3F
+    C20, C20, C22, C11, C20, C20, C22, C11, // 0x40-0x47
+    C21, P12_61, E0_20, E0_20, C21, P12_61, E10_70, E0_20, // 0x48-0x4F
+    C20, C20, C22, C11, C20, C20, C22, C11, // 0x50-0x57
+    C21, C12, E10_70, E0_20, C21, C12, E10_70, E0_20, // 0x58-0x5F
+    C20, C20, C22, C11, C20, C20, C22, C11, // 0x60-0x67
+    C21, P12_61, C10, E0_20, C21, P12_61, C10, E0_100, // 0x68-0x6F
+    C20, C20, C22, C11, C20, C20, C22, N11_60, // 0x70-0x77
+    C21, C12, E10_70, E0_20, C21, P12_61, C10, E0_100, // 0x78-0x7F
+    C20, C20, C22, C11, C20, C20, C22, C11, // 0x80-0x87
+    C21, C12, E10_20, E0_20, C21, C12, E10_90, E0_90, // 0x88-0x8F
+    C20, C20, C22, C11, C20, C20, C22, C11, // 0x90-0x97
+    C21, C12, E10_70, E0_20, C21, C12, E10_70, E0_20, // 0x98-0x9F
+    C20, C20, C22, C11, C20, C20, C22, C11, // 0xA0-0xA7
+    C21, C12, E10_90, E0_90, C21, C12, E10_70, E0_100, // 0xA8-0xAF
+    C20, C20, C22, C11, C20, C20, C22, C11, // 0xB0-0xB7
+    C21, C12, E10_70, E0_90, C21, C12, C10, E0_100, // 0xB8-0xBF
+    C20, C20, C22, C11, C20, C20, C22, C11, // 0xC0-0xC7
+    C21, C12, E10_70, E0_20, C21, C12, E10_70, E0_90, // 0xC8-0xCF
+    C20, C20, C22, C11, C20, C20, C22, C11, // 0xD0-0xD7
+    C21, C12, E10_70, E0_20, C21, C12, C10, E0_20, // 0xD8-0xDF
+    C20, C20, C22, C11, C20, C20, C22, C11, // 0xE0-0xE7
+    C21, C12, E10_70, E0_20, C21, C12, C10, E0_100, // 0xE8-0xEF
+    C20, C20, C22, C11, C20, C20, C22, C11, // 0xF0-0xF7
+    C21, C12, C10, E0_20, C21, C12, C10, E0_100, // 0xF8-0xFF
+];
+
+// hq2x's test for colours that look alike, by their difference in luma and chroma
+fn similar(a: u32, b: u32) -> bool {
+    if a == b {
+        return true;
+    }
+    let (ya, ua, va): (f32, f32, f32) = yuv(a);
+    let (yb, ub, vb): (f32, f32, f32) = yuv(b);
+    (ya - yb).abs() <= 48.0 && (ua - ub).abs() <= 7.0 && (va - vb).abs() <= 6.0
+}
+
+fn yuv(pixel: u32) -> (f32, f32, f32) {
+    let [r, g, b]: [f32; 3] = channels(pixel);
+    (
+        0.299 * r + 0.587 * g + 0.114 * b,
+        -0.169 * r - 0.331 * g + 0.5 * b + 128.0,
+        0.5 * r - 0.419 * g - 0.081 * b + 128.0,
+    )
+}
+
+// Weighted average of some colours
+fn mix(colors: &[(u32, u32)]) -> u32 {
+    let total: f32 = colors.iter().map(|(_, weight)| *weight as f32).sum();
+    let mut sum: [f32; 3] = [0.0; 3];
+    for (color, weight) in colors {
+        for (sum, channel) in sum.iter_mut().zip(channels(*color)) {
+            *sum += channel * *weight as f32;
+        }
+    }
+    color(sum.map(|channel| channel / total))
+}
+
+// Multiply every channel of a colour by a factor
+fn scale_color(pixel: u32, factors: [f32; 3]) -> u32 {
+    let channels: [f32; 3] = channels(pixel);
+    color([
+        channels[0] * factors[0],
+        channels[1] * factors[1],
+        channels[2] * factors[2],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: u32 = 0x000000;
+    const WHITE: u32 = 0xFFFFFF;
+    // White weighted 14 to 2 with black, hq2x's blend for a lone corner
+    const GLOW: u32 = 0xDFDFDF;
+
+    #[test]
+    fn hq2x_leaves_a_flat_image_alone() {
+        let image: Image = Image::new(2, 2, vec![0x336699; 4]);
+        assert_eq!(
+            Filter::Hq2x.apply(&image),
+            Image::new(4, 4, vec![0x336699; 16])
+        );
+    }
+
+    #[test]
+    fn hq2x_rounds_off_a_lone_pixel() {
+        let mut pixels: Vec<u32> = vec![BLACK; 9];
+        pixels[4] = WHITE;
+        let scaled: Image = Filter::Hq2x.apply(&Image::new(3, 3, pixels));
+
+        let mut expected: Vec<u32> = vec![BLACK; 36];
+        for i in [14, 15, 20, 21] {
+            expected[i] = GLOW;
+        }
+        assert_eq!(scaled.pixels, expected);
+    }
+
+    #[test]
+    fn hq2x_keeps_the_inner_side_of_a_line_sharp() {
+        let pixels: Vec<u32> = vec![
+            BLACK, BLACK, BLACK, BLACK, //
+            BLACK, WHITE, WHITE, BLACK, //
+            BLACK, BLACK, BLACK, BLACK, //
+        ];
+        let scaled: Image = Filter::Hq2x.apply(&Image::new(4, 3, pixels));
+
+        // The left end of the line, at (2, 2) in the scaled image
+        let block: [u32; 4] = [
+            scaled.pixels[2 * 8 + 2],
+            scaled.pixels[2 * 8 + 3],
+            scaled.pixels[3 * 8 + 2],
+            scaled.pixels[3 * 8 + 3],
+        ];
+        assert_eq!(block, [GLOW, WHITE, GLOW, WHITE]);
+    }
+
+    #[test]
+    fn filters_are_parsed_by_name() {
+        assert_eq!(
+            parse_filters("hq2x,Scanlines"),
+            Ok(vec![Filter::Hq2x, Filter::Scanlines])
+        );
+        assert!(parse_filters("smooth2x").is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::CHIP8;
+use crate::scale::{self, Filter, Image};
+use std::fmt::Display;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// SCREENSHOTS
+// The display is saved as a PNG in the active palette, with the ROM name, frame number and PC
+// stored in text chunks so the state it was taken in can be found again.
+
+// Save the display as a PNG
+// 'display' is the colour image of the display as it is shown, after any phosphor persistence.
+// It goes through 'filters' and is then enlarged 'scale' times with square pixels.
+pub fn save_png(
+    chip8: &CHIP8,
+    display: &[u32],
+    rom_path: &str,
+    filters: &[Filter],
+    scale: usize,
+    path: &Path,
+) -> Result<(), String> {
+    let image: Image = image(chip8, display, filters, scale);
+    let text: [(&str, String); 4] = [
+        ("Software", String::from("chip8")),
+        ("Title", rom_name(rom_path)),
+        ("Frame", chip8.frame_count.to_string()),
+        ("PC", format!("{:#05x}", chip8.PC)),
+    ];
+    write_png(&image, &text, path)
+}
+
+// The colour image of the display, put through 'filters' and enlarged 'scale' times
+pub fn image(chip8: &CHIP8, display: &[u32], filters: &[Filter], scale: usize) -> Image {
+    let width: usize = chip8.platform.width();
+    let height: usize = chip8.platform.height();
+    scale::apply(filters, Image::new(width, height, display.to_vec())).enlarge(scale)
+}
+
+// Write an image to a new PNG file with some text chunks
+// A file that is already there is left alone and reported as an error
+pub fn write_png(image: &Image, text: &[(&str, String)], path: &Path) -> Result<(), String> {
+    let fail = |error: &dyn Display| format!("could not save '{}': {}", path.display(), error);
+
+    let file: File = File::options()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(|error| fail(&error))?;
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        image.width as u32,
+        image.height as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, value) in text {
+        encoder
+            .add_text_chunk(keyword.to_string(), value.clone())
+            .map_err(|error| fail(&error))?;
+    }
+
+    let mut writer = encoder.write_header().map_err(|error| fail(&error))?;
+    writer
+        .write_image_data(&image.rgb_bytes())
+        .map_err(|error| fail(&error))?;
+    writer.finish().map_err(|error| fail(&error))
+}
+
+// File name of a ROM without its directory and extension
+pub fn rom_name(rom_path: &str) -> String {
+    Path::new(rom_path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("chip8"))
+}
+
+// Name for a new file in the working directory, made from the ROM name and the current time,
+// e.g. "Airplane-20240131-235959.png"
+// The time only has whole seconds, so if that file is already there a number is added to the
+// name instead, e.g. "Airplane-20240131-235959-2.png"
+pub fn timestamped_path(rom_path: &str, extension: &str) -> PathBuf {
+    let seconds: u64 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0);
+    let (year, month, day): (i64, u32, u32) = civil_date((seconds / 86400) as i64);
+    let time: u64 = seconds % 86400;
+
+    let name: String = format!(
+        "{}-{:04}{:02}{:02}-{:02}{:02}{:02}",
+        rom_name(rom_path),
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    );
+
+    (1..)
+        .map(|copy: u32| match copy {
+            1 => PathBuf::from(format!("{}.{}", name, extension)),
+            _ => PathBuf::from(format!("{}-{}.{}", name, copy, extension)),
+        })
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+// Year, month and day (UTC) of a number of days since 1970-01-01
+// This is Howard Hinnant's civil_from_days algorithm
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    let days: i64 = days + 719468;
+    let era: i64 = days.div_euclid(146097);
+    let day_of_era: i64 = days.rem_euclid(146097);
+    let year_of_era: i64 =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year: i64 = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index: i64 = (5 * day_of_year + 2) / 153;
+    let day: u32 = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month: u32 = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year: i64 = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
@@ -0,0 +1,211 @@
+use crate::CHIP8;
+use crate::chip8x;
+use crate::idle::Idle;
+use crate::platform::Platform;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+// SAVE STATES
+// Everything that changes while a program runs, as a flat byte array. The settings the chip was
+// set up with (platform, memory map, timing, palette) aren't saved; a state can only be loaded
+// into a chip set up the same way, which is checked through the platform and memory size. The
+// random number generator can't be saved as it is, so saving reseeds it from a seed stored in
+// the state: running on after saving gives the same random numbers as loading the state does.
+
+const MAGIC: &[u8; 4] = b"C8ST";
+const VERSION: u8 = 1;
+
+impl CHIP8 {
+    // Size of a saved state, which only depends on the platform and memory size
+    pub fn state_size(&self) -> usize {
+        MAGIC.len()
+            + 2 // Version and platform
+            + 4 // Memory size
+            + self.memory.len()
+            + 16 // V0-VF
+            + 2 * 2 // I and PC
+            + 16 * 2 // Stack
+            + 1 // Stack pointer
+            + 2 // Timers
+            + self.platform.height() * 8
+            + 8 // Frame count
+            + 8 // Cycle budget
+            + 2 // Vertical blank flags
+            + 1 // CHIP-8X background
+            + self.color_zones.len()
+            + 8 // Random seed
+    }
+
+    // Save the running state
+    pub fn save_state(&mut self) -> Vec<u8> {
+        let seed: u64 = self.rng.random();
+        self.rng = SmallRng::seed_from_u64(seed);
+        // Loop detection starts over, as it does after loading
+        self.reset_idle();
+
+        let mut state: Vec<u8> = Vec::with_capacity(self.state_size());
+        state.extend_from_slice(MAGIC);
+        state.push(VERSION);
+        state.push(platform_id(self.platform));
+        state.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        state.extend_from_slice(&self.memory);
+        state.extend_from_slice(&self.registers);
+        state.extend_from_slice(&self.IR.to_le_bytes());
+        state.extend_from_slice(&self.PC.to_le_bytes());
+        for entry in self.stack {
+            state.extend_from_slice(&entry.to_le_bytes());
+        }
+        state.push(self.st_pointer as u8);
+        state.push(self.delay_timer);
+        state.push(self.sound_timer);
+        for row in &self.video {
+            state.extend_from_slice(&row.to_le_bytes());
+        }
+        state.extend_from_slice(&self.frame_count.to_le_bytes());
+        state.extend_from_slice(&self.cycle_budget.to_le_bytes());
+        state.push(self.vblank as u8);
+        state.push(self.waiting_for_vblank as u8);
+        state.push(self.background as u8);
+        state.extend_from_slice(&self.color_zones);
+        state.extend_from_slice(&seed.to_le_bytes());
+        state
+    }
+
+    // Go back to a saved state
+    // The chip is left as it was if the state doesn't fit it
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        if state.len() != self.state_size() || !state.starts_with(MAGIC) {
+            return Err(String::from("not a save state for this ROM"));
+        }
+        let mut reader: Reader = Reader {
+            bytes: &state[MAGIC.len()..],
+        };
+        if reader.byte() != VERSION {
+            return Err(String::from("save state from another version"));
+        }
+        if reader.byte() != platform_id(self.platform) || reader.u32() as usize != self.memory.len()
+        {
+            return Err(String::from(
+                "save state for another platform or memory size",
+            ));
+        }
+
+        // Read everything before changing anything, so a damaged state can still be turned down
+        let memory: &[u8] = reader.take(self.memory.len());
+        let registers: &[u8] = reader.take(16);
+        let ir: u16 = reader.u16();
+        let pc: u16 = reader.u16();
+        let mut stack: [u16; 16] = [0; 16];
+        for entry in stack.iter_mut() {
+            *entry = reader.u16();
+        }
+        let st_pointer: usize = reader.byte() as usize;
+        let delay_timer: u8 = reader.byte();
+        let sound_timer: u8 = reader.byte();
+        let video: Vec<u64> = (0..self.video.len()).map(|_| reader.u64()).collect();
+        let frame_count: u64 = reader.u64();
+        let cycle_budget: i64 = reader.u64() as i64;
+        let vblank: bool = reader.byte() != 0;
+        let waiting_for_vblank: bool = reader.byte() != 0;
+        let background: usize = reader.byte() as usize;
+        let color_zones: &[u8] = reader.take(self.color_zones.len());
+        let seed: u64 = reader.u64();
+
+        if st_pointer >= stack.len()
+            || background >= chip8x::BACKGROUNDS.len()
+            || color_zones
+                .iter()
+                .any(|color| *color as usize >= chip8x::COLORS.len())
+        {
+            return Err(String::from("save state is damaged"));
+        }
+
+        self.memory.copy_from_slice(memory);
+        self.registers.copy_from_slice(registers);
+        self.IR = ir;
+        self.PC = pc;
+        self.stack = stack;
+        self.st_pointer = st_pointer;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.video = video;
+        self.frame_count = frame_count;
+        self.cycle_budget = cycle_budget;
+        self.vblank = vblank;
+        self.waiting_for_vblank = waiting_for_vblank;
+        self.background = background;
+        self.color_zones.copy_from_slice(color_zones);
+        self.rng = SmallRng::seed_from_u64(seed);
+
+        self.reset_idle();
+        self.clear_decode_cache();
+        self.display_dirty = true;
+        Ok(())
+    }
+
+    fn reset_idle(&mut self) {
+        self.idle = Idle::Running;
+        self.loop_state = None;
+        self.side_effect = false;
+    }
+}
+
+fn platform_id(platform: Platform) -> u8 {
+    match platform {
+        Platform::Chip8 => 0,
+        Platform::HiRes => 1,
+        Platform::Chip8X => 2,
+    }
+}
+
+// Reads little-endian values off the front of a byte slice whose length has been checked
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> &'a [u8] {
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        taken
+    }
+
+    fn byte(&mut self) -> u8 {
+        self.take(1)[0]
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.take(2).try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take(4).try_into().unwrap())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take(8).try_into().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn damaged_state_is_turned_down_and_leaves_the_chip_alone() {
+        let mut chip8: CHIP8 = CHIP8::new();
+        chip8.registers[3] = 3;
+        let mut state: Vec<u8> = chip8.save_state();
+        chip8.registers[3] = 4;
+
+        // The background comes just before the colour zones and the seed
+        let background: usize = state.len() - 8 - chip8.color_zones.len() - 1;
+        state[background] = chip8x::BACKGROUNDS.len() as u8;
+        assert!(chip8.load_state(&state).is_err());
+        assert_eq!(chip8.registers[3], 4);
+
+        state[background] = 0;
+        assert!(chip8.load_state(&state).is_ok());
+        assert_eq!(chip8.registers[3], 3);
+    }
+}
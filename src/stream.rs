@@ -0,0 +1,209 @@
+use crate::palette::channels;
+use crate::scale::Image;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+// RAW STREAMS
+// Video and audio written frame by frame in formats external encoders read straight from a pipe,
+// e.g. ffmpeg -i video.y4m -f s16le -ar 44100 -ac 1 -i audio.pcm out.mp4
+
+// Rate frames are written at, the CHIP-8's 60 Hz
+const FRAME_RATE: u32 = 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VideoFormat {
+    // YUV4MPEG2 with full chroma (C444), which keeps the pixel edges sharp
+    Y4m,
+    // Bare RGBA bytes; the reader has to be told the size and rate
+    Rgba,
+}
+
+impl FromStr for VideoFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<VideoFormat, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "y4m" => Ok(VideoFormat::Y4m),
+            "rgba" => Ok(VideoFormat::Rgba),
+            _ => Err(format!("unknown video format '{}'", name)),
+        }
+    }
+}
+
+pub struct VideoWriter<W: Write> {
+    output: W,
+    format: VideoFormat,
+    header_written: bool,
+}
+
+impl<W: Write> VideoWriter<W> {
+    pub fn new(output: W, format: VideoFormat) -> VideoWriter<W> {
+        VideoWriter {
+            output,
+            format,
+            header_written: false,
+        }
+    }
+
+    // Write one 60 Hz frame
+    // Every frame has to be the same size as the first
+    pub fn write_frame(&mut self, image: &Image) -> io::Result<()> {
+        match self.format {
+            VideoFormat::Y4m => {
+                if !self.header_written {
+                    writeln!(
+                        self.output,
+                        "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                        image.width, image.height, FRAME_RATE
+                    )?;
+                    self.header_written = true;
+                }
+                // The Y, U and V planes follow each other
+                let yuv: Vec<[u8; 3]> = image.pixels.iter().map(|pixel| yuv(*pixel)).collect();
+                let mut frame: Vec<u8> = Vec::with_capacity(yuv.len() * 3);
+                for plane in 0..3 {
+                    frame.extend(yuv.iter().map(|pixel| pixel[plane]));
+                }
+                self.output.write_all(b"FRAME\n")?;
+                self.output.write_all(&frame)?;
+            }
+            VideoFormat::Rgba => {
+                let frame: Vec<u8> = image
+                    .pixels
+                    .iter()
+                    .flat_map(|pixel| {
+                        let [_, red, green, blue]: [u8; 4] = pixel.to_be_bytes();
+                        [red, green, blue, 0xFF]
+                    })
+                    .collect();
+                self.output.write_all(&frame)?;
+            }
+        }
+        self.output.flush()
+    }
+}
+
+// Convert a 0RGB colour to studio range BT.601 Y, U and V
+fn yuv(pixel: u32) -> [u8; 3] {
+    let [r, g, b]: [f32; 3] = channels(pixel);
+    [
+        (16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0).round() as u8,
+        (128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0).round() as u8,
+        (128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0).round() as u8,
+    ]
+}
+
+// Square wave tone for the sound timer, as 16-bit signed little-endian mono PCM
+// The VIP only has a beeper, so the pitch is not meant to match anything in particular
+pub struct Beeper {
+    sample_rate: u32,
+    frequency: f64,
+    phase: f64, // Position in the current period, from 0 to 1
+}
+
+pub const SAMPLE_RATE: u32 = 44100;
+const TONE_FREQUENCY: f64 = 440.0;
+const VOLUME: i16 = i16::MAX / 4;
+
+impl Default for Beeper {
+    fn default() -> Beeper {
+        Beeper {
+            sample_rate: SAMPLE_RATE,
+            frequency: TONE_FREQUENCY,
+            phase: 0.0,
+        }
+    }
+}
+
+impl Beeper {
+    // The samples for one 60 Hz frame, with the tone on or off
+    // The tone keeps its phase across frames so it doesn't click
+    pub fn frame(&mut self, on: bool) -> Vec<u8> {
+        let samples: u32 = self.sample_rate / FRAME_RATE;
+        let step: f64 = self.frequency / self.sample_rate as f64;
+        let mut bytes: Vec<u8> = Vec::with_capacity(samples as usize * 2);
+
+        for _ in 0..samples {
+            let sample: i16 = match (on, self.phase < 0.5) {
+                (false, _) => 0,
+                (true, true) => VOLUME,
+                (true, false) => -VOLUME,
+            };
+            bytes.extend_from_slice(&sample.to_le_bytes());
+            self.phase = (self.phase + step) % 1.0;
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Red, white and black side by side
+    fn image() -> Image {
+        Image::new(3, 1, vec![0xFF0000, 0xFFFFFF, 0x000000])
+    }
+
+    #[test]
+    fn y4m_has_one_header_then_planar_frames() {
+        let mut writer: VideoWriter<Vec<u8>> = VideoWriter::new(Vec::new(), VideoFormat::Y4m);
+        writer.write_frame(&image()).unwrap();
+        writer.write_frame(&image()).unwrap();
+
+        let frame: [u8; 15] = [
+            b'F', b'R', b'A', b'M', b'E', b'\n', 81, 235, 16, 90, 128, 128, 240, 128, 128,
+        ];
+        let mut expected: Vec<u8> = b"YUV4MPEG2 W3 H1 F60:1 Ip A1:1 C444\n".to_vec();
+        expected.extend_from_slice(&frame);
+        expected.extend_from_slice(&frame);
+        assert_eq!(writer.output, expected);
+    }
+
+    #[test]
+    fn rgba_is_bare_opaque_pixels() {
+        let mut writer: VideoWriter<Vec<u8>> = VideoWriter::new(Vec::new(), VideoFormat::Rgba);
+        writer.write_frame(&image()).unwrap();
+        assert_eq!(
+            writer.output,
+            vec![255, 0, 0, 255, 255, 255, 255, 255, 0, 0, 0, 255]
+        );
+    }
+
+    // The first three runs of equal samples, with their lengths
+    fn runs(bytes: &[u8]) -> Vec<(i16, usize)> {
+        let mut runs: Vec<(i16, usize)> = Vec::new();
+        for sample in bytes
+            .chunks(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        {
+            match runs.last_mut() {
+                Some((value, length)) if *value == sample => *length += 1,
+                _ => runs.push((sample, 1)),
+            }
+        }
+        runs.truncate(3);
+        runs
+    }
+
+    #[test]
+    fn beeper_writes_a_frame_of_square_wave() {
+        let mut beeper: Beeper = Beeper::default();
+        let on: Vec<u8> = beeper.frame(true);
+        assert_eq!(on.len(), 735 * 2);
+        // 440 Hz at 44100 Hz is a little over 100 samples per period
+        assert_eq!(runs(&on), vec![(8191, 51), (-8191, 50), (8191, 50)]);
+
+        // 7 1/3 periods went by, so the next frame starts a third of the way into one
+        assert_eq!(
+            runs(&beeper.frame(true)),
+            vec![(8191, 17), (-8191, 50), (8191, 50)]
+        );
+    }
+
+    #[test]
+    fn beeper_is_silent_when_off() {
+        let mut beeper: Beeper = Beeper::default();
+        assert_eq!(beeper.frame(false), vec![0; 735 * 2]);
+    }
+}
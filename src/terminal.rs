@@ -0,0 +1,340 @@
+use crate::frontend::{Control, Frontend};
+use chip8::keymap;
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, execute, queue};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+// TERMINAL FRONTEND
+// Shows the display in a text terminal with 24-bit ANSI colours, e.g. over SSH. Most terminals
+// only report key presses, not releases, so a key counts as held for a short while after each
+// press and autorepeat keeps it held. Terminals that can report releases (the kitty keyboard
+// protocol) are asked to. The CHIP-8X second keypad isn't available, as terminals send the same
+// characters for the numeric keypad as for the digit keys.
+
+// How long a key counts as held after a press when the terminal doesn't report releases
+// Long enough to bridge the delay before autorepeat starts
+const KEY_HOLD: Duration = Duration::from_millis(550);
+
+// Host key for every frontend control
+fn control_key(control: Control) -> KeyCode {
+    match control {
+        Control::Quit => KeyCode::Esc,
+        Control::FastForward => KeyCode::Tab,
+        Control::Pause => KeyCode::Char('p'),
+        Control::FrameAdvance => KeyCode::Char('n'),
+        Control::Slower => KeyCode::Char('-'),
+        Control::Faster => KeyCode::Char('='),
+        Control::Palette => KeyCode::F(2),
+        Control::Screenshot => KeyCode::F(12),
+        Control::Record => KeyCode::F(9),
+    }
+}
+
+// Characters the pixels are drawn with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Glyphs {
+    // Upper half blocks, one character for 1 by 2 pixels in two colours
+    HalfBlock,
+    // Braille patterns, one character for 2 by 4 pixels in one colour on the background
+    Braille,
+}
+
+impl FromStr for Glyphs {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Glyphs, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "halfblock" | "half-block" => Ok(Glyphs::HalfBlock),
+            "braille" => Ok(Glyphs::Braille),
+            _ => Err(format!("unknown terminal glyphs '{}'", name)),
+        }
+    }
+}
+
+// State of a host key, from the events read so far
+#[derive(Default)]
+struct KeyState {
+    down: bool,
+    pressed_at: Option<Instant>,
+    presses: u32, // Presses since the key was last asked about
+    repeats: u32, // Autorepeats since the key was last asked about
+}
+
+pub struct Terminal {
+    glyphs: Glyphs,
+    // Whether the terminal reports key releases
+    releases: bool,
+    open: bool,
+    keys: HashMap<KeyCode, KeyState>,
+    // Last image shown, to draw again when the terminal is resized
+    image: Option<(Vec<u32>, usize, usize)>,
+    title: String,
+    message: String,
+}
+
+impl Terminal {
+    // Switch the terminal to raw mode and an alternate screen; dropping it switches back
+    pub fn new(glyphs: Glyphs) -> io::Result<Terminal> {
+        let releases: bool = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        terminal::enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        execute!(
+            stdout,
+            terminal::EnterAlternateScreen,
+            cursor::Hide,
+            terminal::Clear(ClearType::All)
+        )?;
+        if releases {
+            execute!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+
+        Ok(Terminal {
+            glyphs,
+            releases,
+            open: true,
+            keys: HashMap::new(),
+            image: None,
+            title: String::from("CHIP8"),
+            message: String::new(),
+        })
+    }
+
+    // Read every waiting event without blocking
+    fn read_events(&mut self) {
+        while let Ok(true) = event::poll(Duration::ZERO) {
+            match event::read() {
+                Ok(Event::Key(key)) => self.key_event(key),
+                Ok(Event::Resize(_, _)) => {
+                    let _ = queue!(io::stdout(), terminal::Clear(ClearType::All));
+                    self.draw();
+                }
+                Ok(_) => {}
+                Err(_) => self.open = false,
+            }
+        }
+    }
+
+    fn key_event(&mut self, key: KeyEvent) {
+        // Ctrl+C no longer interrupts in raw mode, so it quits instead
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            self.open = false;
+            return;
+        }
+        let code: KeyCode = match key.code {
+            KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+            code => code,
+        };
+        let held: bool = self.is_key_down(code);
+        let state: &mut KeyState = self.keys.entry(code).or_default();
+        match key.kind {
+            // Without release events a press of a key that is still held is an autorepeat
+            KeyEventKind::Press if !held => {
+                state.presses += 1;
+                state.down = true;
+                state.pressed_at = Some(Instant::now());
+            }
+            KeyEventKind::Press | KeyEventKind::Repeat => {
+                state.repeats += 1;
+                state.down = true;
+                state.pressed_at = Some(Instant::now());
+            }
+            KeyEventKind::Release => state.down = false,
+        }
+    }
+
+    fn is_key_down(&self, code: KeyCode) -> bool {
+        self.keys.get(&code).is_some_and(|state| {
+            state.down
+                && (self.releases
+                    || state
+                        .pressed_at
+                        .is_some_and(|pressed_at| pressed_at.elapsed() < KEY_HOLD))
+        })
+    }
+
+    // Draw the last image and the status line below it
+    fn draw(&mut self) {
+        let Some((pixels, width, height)) = &self.image else {
+            return;
+        };
+        let mut output: Vec<u8> = Vec::new();
+        let rows: u16 = match self.glyphs {
+            Glyphs::HalfBlock => draw_half_blocks(&mut output, pixels, *width, *height),
+            Glyphs::Braille => draw_braille(&mut output, pixels, *width, *height),
+        };
+
+        let status: String = if self.message.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{}  {}", self.title, self.message)
+        };
+        let _ = queue!(
+            output,
+            ResetColor,
+            cursor::MoveTo(0, rows),
+            terminal::Clear(ClearType::UntilNewLine),
+            Print(status)
+        );
+
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(&output);
+        let _ = stdout.flush();
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        if self.releases {
+            let _ = execute!(stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(
+            stdout,
+            ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl Frontend for Terminal {
+    fn is_open(&self) -> bool {
+        self.open
+    }
+
+    fn read_keypads(&mut self, keypad: &mut [bool; 16], _keypad2: &mut [bool; 16]) {
+        for (key, host_key) in keymap::KEYPAD.iter().enumerate() {
+            keypad[key] = self.is_key_down(KeyCode::Char(*host_key));
+        }
+    }
+
+    fn is_down(&self, control: Control) -> bool {
+        self.is_key_down(control_key(control))
+    }
+
+    fn was_pressed(&mut self, control: Control, repeat: bool) -> bool {
+        match self.keys.get_mut(&control_key(control)) {
+            Some(state) => {
+                let pressed: bool = state.presses > 0 || (repeat && state.repeats > 0);
+                state.presses = 0;
+                state.repeats = 0;
+                pressed
+            }
+            None => false,
+        }
+    }
+
+    fn present(&mut self, pixels: &[u32], width: usize, height: usize) {
+        self.image = Some((pixels.to_vec(), width, height));
+        self.draw();
+        self.read_events();
+    }
+
+    fn update(&mut self) {
+        self.read_events();
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
+        let _ = execute!(io::stdout(), terminal::SetTitle(title));
+        self.draw();
+    }
+
+    fn notify(&mut self, message: &str) {
+        self.message = message.to_string();
+        self.draw();
+    }
+}
+
+fn rgb(pixel: u32) -> Color {
+    let [_, r, g, b]: [u8; 4] = pixel.to_be_bytes();
+    Color::Rgb { r, g, b }
+}
+
+// Draw every two rows of pixels as a row of upper half blocks, the top pixel in the foreground
+// colour and the bottom one in the background colour
+// Returns the number of text rows drawn
+fn draw_half_blocks(output: &mut Vec<u8>, pixels: &[u32], width: usize, height: usize) -> u16 {
+    let rows: usize = height.div_ceil(2);
+    for row in 0..rows {
+        let _ = queue!(output, cursor::MoveTo(0, row as u16));
+        // Colours are only sent when they change
+        let mut colors: Option<(u32, u32)> = None;
+        for x in 0..width {
+            let top: u32 = pixels[row * 2 * width + x];
+            let bottom: u32 = pixels
+                .get((row * 2 + 1) * width + x)
+                .copied()
+                .unwrap_or(top);
+            if colors != Some((top, bottom)) {
+                let _ = queue!(
+                    output,
+                    SetForegroundColor(rgb(top)),
+                    SetBackgroundColor(rgb(bottom))
+                );
+                colors = Some((top, bottom));
+            }
+            let _ = queue!(output, Print('▀'));
+        }
+    }
+    rows as u16
+}
+
+// Draw every 2 by 4 block of pixels as a braille pattern, with a dot for every pixel that isn't
+// the background colour (the most common one)
+// Returns the number of text rows drawn
+fn draw_braille(output: &mut Vec<u8>, pixels: &[u32], width: usize, height: usize) -> u16 {
+    // Bit of every dot in the pattern, by its row and column
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for pixel in pixels {
+        *counts.entry(*pixel).or_default() += 1;
+    }
+    let background: u32 = counts
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map_or(0, |(pixel, _)| pixel);
+
+    let rows: usize = height.div_ceil(4);
+    let _ = queue!(output, SetBackgroundColor(rgb(background)));
+    for row in 0..rows {
+        let _ = queue!(output, cursor::MoveTo(0, row as u16));
+        let mut color: Option<u32> = None;
+        for column in 0..width.div_ceil(2) {
+            let mut pattern: u32 = 0;
+            // The dots take the colour of the first lit pixel
+            let mut foreground: Option<u32> = None;
+            for (dy, bits) in DOTS.iter().enumerate() {
+                for (dx, bit) in bits.iter().enumerate() {
+                    let (x, y): (usize, usize) = (column * 2 + dx, row * 4 + dy);
+                    if x < width && y < height && pixels[y * width + x] != background {
+                        pattern |= bit;
+                        foreground.get_or_insert(pixels[y * width + x]);
+                    }
+                }
+            }
+            if let Some(foreground) = foreground
+                && color != Some(foreground)
+            {
+                let _ = queue!(output, SetForegroundColor(rgb(foreground)));
+                color = Some(foreground);
+            }
+            let glyph: char = char::from_u32(0x2800 + pattern).unwrap_or(' ');
+            let _ = queue!(output, Print(glyph));
+        }
+    }
+    rows as u16
+}
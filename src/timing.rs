@@ -0,0 +1,131 @@
+use std::str::FromStr;
+
+// COSMAC VIP TIMING
+// The VIP's 1802 runs at 1.7609 MHz and needs 8 clock cycles for one machine cycle, which gives
+// 3668 machine cycles per 60 Hz frame. The 1861 video chip steals 1024 of them for display DMA
+// (32 rows of 8 bytes, each shown on 4 scan lines) and the interrupt routine takes 46 more.
+pub const VIP_CYCLES_PER_FRAME: i64 = 3668;
+const VIP_DISPLAY_CYCLES: i64 = 1024 + 46;
+// Machine cycles left for the interpreter in each frame
+pub const VIP_AVAILABLE_CYCLES: i64 = VIP_CYCLES_PER_FRAME - VIP_DISPLAY_CYCLES;
+
+// Machine cycles the interpreter spends fetching and decoding every instruction
+const FETCH_CYCLES: u32 = 40;
+
+// Dxyn costs a fixed setup, plus a cost per row that grows with the number of bit shifts
+// needed to line the sprite up with the display bytes, plus a second display byte per row
+// when the sprite isn't byte aligned
+const DRAW_SETUP_CYCLES: u32 = 26;
+const DRAW_ROW_CYCLES: u32 = 21;
+const DRAW_SHIFT_CYCLES: u32 = 3;
+const DRAW_SPLIT_CYCLES: u32 = 12;
+
+// How fast instructions are executed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimingModel {
+    // Run as many instructions per frame as their VIP machine cycle costs allow
+    Vip,
+    // Run a fixed number of instructions per frame
+    InstructionsPerFrame(u32),
+}
+
+impl FromStr for TimingModel {
+    type Err = String;
+
+    // "vip" for authentic speed, or a number of instructions per frame
+    fn from_str(spec: &str) -> Result<TimingModel, String> {
+        if spec.eq_ignore_ascii_case("vip") {
+            return Ok(TimingModel::Vip);
+        }
+        match spec.parse() {
+            Ok(count) if count > 0 => Ok(TimingModel::InstructionsPerFrame(count)),
+            _ => Err(format!("unknown timing '{}'", spec)),
+        }
+    }
+}
+
+// Approximate VIP machine cycles needed to execute an instruction, after the analysis of the
+// VIP interpreter's routines.
+// 'sprite_x' is the horizontal position Dxyn draws at and 'sprite_rows' the number of rows that
+// end up on screen; both are ignored for other instructions.
+pub fn vip_cycles(opcode: u16, sprite_x: u8, sprite_rows: u32) -> u32 {
+    let execute: u32 = match (opcode & 0xF000) >> 12 {
+        0x0 => match opcode {
+            0x00E0 => 24,
+            0x00EE => 10,
+            // Machine code subroutines can't be timed; count them as a call
+            _ => 10,
+        },
+        0x1 => 12,
+        0x2 => 26,
+        0x3 | 0x4 => 10,
+        0x5 | 0x9 => 14,
+        0x6 => 6,
+        0x7 => 10,
+        0x8 => 44,
+        0xA => 12,
+        0xB => 22,
+        0xC => 36,
+        0xD => {
+            let shift: u32 = (sprite_x % 8) as u32;
+            let row: u32 = DRAW_ROW_CYCLES
+                + shift * DRAW_SHIFT_CYCLES
+                + if shift != 0 { DRAW_SPLIT_CYCLES } else { 0 };
+            DRAW_SETUP_CYCLES + sprite_rows * row
+        }
+        0xE => 14,
+        0xF => match opcode & 0x00FF {
+            0x07 | 0x15 | 0x18 => 10,
+            0x0A => 18,
+            0x1E => 18,
+            0x29 => 20,
+            0x33 => 204,
+            // Fx55 and Fx65 copy one register per loop
+            0x55 | 0x65 => 28 + 14 * ((opcode & 0x0F00) >> 8) as u32,
+            _ => 10,
+        },
+        _ => 10,
+    };
+
+    FETCH_CYCLES + execute
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instructions_cost_their_fetch_and_execution() {
+        assert_eq!(vip_cycles(0x00E0, 0, 0), 64);
+        assert_eq!(vip_cycles(0x00EE, 0, 0), 50);
+        assert_eq!(vip_cycles(0x1200, 0, 0), 52);
+        assert_eq!(vip_cycles(0x6012, 0, 0), 46);
+        assert_eq!(vip_cycles(0x8014, 0, 0), 84);
+        assert_eq!(vip_cycles(0xF033, 0, 0), 244);
+    }
+
+    #[test]
+    fn fx55_and_fx65_cost_one_loop_per_register() {
+        assert_eq!(vip_cycles(0xF055, 0, 0), 68);
+        assert_eq!(vip_cycles(0xF355, 0, 0), 110);
+        assert_eq!(vip_cycles(0xFF65, 0, 0), 278);
+    }
+
+    #[test]
+    fn sprites_cost_more_per_row_when_shifted() {
+        // Byte aligned: 26 + 5 * 21
+        assert_eq!(vip_cycles(0xD015, 8, 5), 171);
+        // Shifted by 3: 26 + 5 * (21 + 3 * 3 + 12)
+        assert_eq!(vip_cycles(0xD015, 11, 5), 276);
+        // Nothing on screen still costs the setup
+        assert_eq!(vip_cycles(0xD015, 11, 0), 66);
+    }
+
+    #[test]
+    fn timing_is_vip_or_a_positive_count() {
+        assert_eq!("VIP".parse(), Ok(TimingModel::Vip));
+        assert_eq!("15".parse(), Ok(TimingModel::InstructionsPerFrame(15)));
+        assert!("0".parse::<TimingModel>().is_err());
+        assert!("fast".parse::<TimingModel>().is_err());
+    }
+}
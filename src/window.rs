@@ -0,0 +1,152 @@
+use crate::frontend::{Control, Frontend};
+use chip8::keymap;
+use minifb::Key;
+use minifb::KeyRepeat;
+use minifb::Scale;
+use minifb::WindowOptions;
+
+// minifb key for a key of the main keypad block in chip8::keymap
+fn keypad_key(key: char) -> Key {
+    match key {
+        '1' => Key::Key1,
+        '2' => Key::Key2,
+        '3' => Key::Key3,
+        '4' => Key::Key4,
+        'q' => Key::Q,
+        'w' => Key::W,
+        'e' => Key::E,
+        'r' => Key::R,
+        'a' => Key::A,
+        's' => Key::S,
+        'd' => Key::D,
+        'f' => Key::F,
+        'z' => Key::Z,
+        'x' => Key::X,
+        'c' => Key::C,
+        'v' => Key::V,
+        _ => Key::Unknown,
+    }
+}
+
+// minifb key for a numeric keypad key of the second keypad in chip8::keymap
+fn keypad2_key(key: char) -> Key {
+    match key {
+        '0' => Key::NumPad0,
+        '1' => Key::NumPad1,
+        '2' => Key::NumPad2,
+        '3' => Key::NumPad3,
+        '4' => Key::NumPad4,
+        '5' => Key::NumPad5,
+        '6' => Key::NumPad6,
+        '7' => Key::NumPad7,
+        '8' => Key::NumPad8,
+        '9' => Key::NumPad9,
+        '.' => Key::NumPadDot,
+        '\n' => Key::NumPadEnter,
+        '/' => Key::NumPadSlash,
+        '*' => Key::NumPadAsterisk,
+        '-' => Key::NumPadMinus,
+        '+' => Key::NumPadPlus,
+        _ => Key::Unknown,
+    }
+}
+
+// Host key for every frontend control
+fn control_key(control: Control) -> Key {
+    match control {
+        Control::Quit => Key::Escape,
+        Control::FastForward => Key::Tab,
+        Control::Pause => Key::P,
+        Control::FrameAdvance => Key::N,
+        Control::Slower => Key::Minus,
+        Control::Faster => Key::Equal,
+        Control::Palette => Key::F2,
+        Control::Screenshot => Key::F12,
+        Control::Record => Key::F9,
+    }
+}
+
+// The display in a desktop window
+pub struct Window {
+    window: minifb::Window,
+}
+
+impl Window {
+    // Open a window for a display of 'width' by 'height' pixels that filters make 'factor' times
+    // larger
+    pub fn new(width: usize, height: usize, factor: usize) -> Window {
+        // Filters make the image larger, so the window scales it up less to keep about the same size
+        let window_scale: Scale = match 8 / factor {
+            0 | 1 => Scale::X1,
+            2 | 3 => Scale::X2,
+            4..=7 => Scale::X4,
+            _ => Scale::X8,
+        };
+
+        let mut window = minifb::Window::new(
+            "CHIP8",
+            width * factor,
+            height * factor,
+            WindowOptions {
+                borderless: false,
+                resize: true,
+                scale: window_scale,
+                scale_mode: minifb::ScaleMode::AspectRatioStretch,
+                topmost: false,
+                ..WindowOptions::default()
+            },
+        )
+        .unwrap();
+
+        // Frames are paced by the FramePacer, so minifb must not sleep on its own
+        window.set_target_fps(0);
+
+        Window { window }
+    }
+}
+
+impl Frontend for Window {
+    fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    fn read_keypads(&mut self, keypad: &mut [bool; 16], keypad2: &mut [bool; 16]) {
+        for (key, host_key) in keymap::KEYPAD.iter().enumerate() {
+            keypad[key] = self.window.is_key_down(keypad_key(*host_key));
+        }
+        for (key, host_key) in keymap::KEYPAD2.iter().enumerate() {
+            keypad2[key] = self.window.is_key_down(keypad2_key(*host_key));
+        }
+    }
+
+    fn is_down(&self, control: Control) -> bool {
+        self.window.is_key_down(control_key(control))
+    }
+
+    fn was_pressed(&mut self, control: Control, repeat: bool) -> bool {
+        let repeat: KeyRepeat = if repeat {
+            KeyRepeat::Yes
+        } else {
+            KeyRepeat::No
+        };
+        self.window.is_key_pressed(control_key(control), repeat)
+    }
+
+    fn present(&mut self, pixels: &[u32], width: usize, height: usize) {
+        self.window
+            .update_with_buffer(pixels, width, height)
+            .unwrap();
+    }
+
+    fn update(&mut self) {
+        self.window.update();
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    fn notify(&mut self, message: &str) {
+        println!("{}", message);
+    }
+}
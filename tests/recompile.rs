@@ -0,0 +1,130 @@
+// RECOMPILER TEST
+// Every ROM bundled in the crate root is recompiled with chip8-recompile, and the blocks are
+// built into a small program that runs them next to the interpreter. The program is a crate of
+// its own, so this test needs cargo; it is built in the test's scratch directory and only
+// rebuilds what changed on later runs.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+// Frames each ROM is run for
+const FRAMES: u32 = 600;
+
+// Runs every ROM with and without its recompiled blocks, with the same keys held down, and
+// checks the saved state after every frame
+const MAIN: &str = r#"
+fn main() {
+    let mut failed: bool = false;
+    for (rom_path, install) in ROMS {
+        let rom: Vec<u8> = std::fs::read(rom_path).unwrap();
+        let mut interpreted: CHIP8 = CHIP8::new();
+        let mut recompiled: CHIP8 = CHIP8::new();
+        for chip8 in [&mut interpreted, &mut recompiled] {
+            chip8.seed(0);
+            CHIP8::load_rom_bytes(chip8, &rom, None, None).unwrap();
+        }
+        install(&mut recompiled);
+
+        for frame in 0..FRAMES {
+            // A different key every few frames, so programs waiting for one get on
+            let key: usize = (frame / 10) as usize % 16;
+            for chip8 in [&mut interpreted, &mut recompiled] {
+                chip8.keypad = [false; 16];
+                chip8.keypad[key] = true;
+                chip8.run_frame();
+            }
+            if interpreted.save_state() != recompiled.save_state() {
+                eprintln!("{}: state differs after frame {}", rom_path, frame + 1);
+                failed = true;
+                break;
+            }
+        }
+    }
+    std::process::exit(failed as i32);
+}
+"#;
+
+// The ROMs bundled in the crate root
+fn bundled_roms() -> Vec<PathBuf> {
+    let mut roms: Vec<PathBuf> = fs::read_dir(env!("CARGO_MANIFEST_DIR"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "ch8"))
+        .collect();
+    roms.sort();
+    roms
+}
+
+// Fail with the output of a command that didn't succeed
+fn check(what: &str, output: &Output) {
+    assert!(
+        output.status.success(),
+        "{} failed:\n{}",
+        what,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn recompiled_roms_match_the_interpreter() {
+    let roms: Vec<PathBuf> = bundled_roms();
+    assert!(
+        !roms.is_empty(),
+        "no ROMs in {}",
+        env!("CARGO_MANIFEST_DIR")
+    );
+
+    let project: PathBuf = Path::new(env!("CARGO_TARGET_TMPDIR")).join("recompile");
+    let source: PathBuf = project.join("src");
+    fs::create_dir_all(&source).unwrap();
+
+    // The crate is built against this one, with the versions this one was built with
+    // It sits under the target directory, so it says it isn't part of this workspace
+    let manifest: String = format!(
+        "[package]\nname = \"recompiled\"\nversion = \"0.1.0\"\nedition = \"2024\"\n\n\
+         [dependencies]\nchip8 = {{ path = {:?}, default-features = false }}\n\n[workspace]\n",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    fs::write(project.join("Cargo.toml"), manifest).unwrap();
+    let lock_file: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.lock");
+    if lock_file.exists() {
+        fs::copy(&lock_file, project.join("Cargo.lock")).unwrap();
+    }
+
+    let mut main: String = String::from("use chip8::CHIP8;\n\n");
+    for i in 0..roms.len() {
+        writeln!(main, "mod blocks_{};", i).unwrap();
+    }
+    writeln!(main, "\nconst FRAMES: u32 = {};", FRAMES).unwrap();
+    writeln!(
+        main,
+        "const ROMS: [(&str, fn(&mut CHIP8)); {}] = [",
+        roms.len()
+    )
+    .unwrap();
+    for (i, rom) in roms.iter().enumerate() {
+        let output: Output = Command::new(env!("CARGO_BIN_EXE_chip8-recompile"))
+            .arg(rom)
+            .output()
+            .unwrap();
+        check(&format!("chip8-recompile {}", rom.display()), &output);
+        fs::write(source.join(format!("blocks_{}.rs", i)), &output.stdout).unwrap();
+
+        writeln!(main, "    ({:?}, blocks_{}::install),", rom, i).unwrap();
+    }
+    main.push_str("];\n");
+    main.push_str(MAIN);
+    fs::write(source.join("main.rs"), main).unwrap();
+
+    let cargo: String = env::var("CARGO").unwrap_or_else(|_| String::from("cargo"));
+    let output: Output = Command::new(cargo)
+        .args(["run", "--quiet", "--manifest-path"])
+        .arg(project.join("Cargo.toml"))
+        .env("CARGO_TARGET_DIR", project.join("target"))
+        .output()
+        .unwrap();
+    check("the recompiled ROMs", &output);
+}
@@ -0,0 +1,103 @@
+// Browser frontend: loads a ROM from the file picker and runs it on the canvas
+// The emulator is the wasm-bindgen output in pkg/ (see web/Cargo.toml for how to build it)
+
+import init, { Emulator, keypad_key } from "./pkg/chip8_web.js";
+
+const FRAME_TIME = 1000 / 60;
+// Frames run back to back when the page falls behind, e.g. after being in a background tab
+const MAX_CATCH_UP_FRAMES = 4;
+
+const canvas = document.getElementById("screen");
+const context = canvas.getContext("2d");
+
+let emulator = null;
+let lastTime = null;
+let pending = 0; // Time not yet used up by whole frames
+
+// The beeper is a square wave that is muted while the sound timer is zero
+// Browsers only allow audio to start after the user has interacted with the page
+let audio = null;
+let gain = null;
+function startAudio() {
+  if (audio) {
+    return;
+  }
+  audio = new AudioContext();
+  const oscillator = audio.createOscillator();
+  oscillator.type = "square";
+  oscillator.frequency.value = 440;
+  gain = audio.createGain();
+  gain.gain.value = 0;
+  oscillator.connect(gain).connect(audio.destination);
+  oscillator.start();
+}
+
+// The keys are laid out as in the desktop frontends, with the CHIP-8X second keypad on the
+// numeric keypad
+function setKey(event, down) {
+  if (!emulator) {
+    return;
+  }
+  const numpad = event.location === KeyboardEvent.DOM_KEY_LOCATION_NUMPAD;
+  const key = keypad_key(event.key, numpad);
+  if (key === undefined) {
+    return;
+  }
+  if (numpad) {
+    emulator.set_key2(key, down);
+  } else {
+    emulator.set_key(key, down);
+  }
+  event.preventDefault();
+}
+document.addEventListener("keydown", (event) => setKey(event, true));
+document.addEventListener("keyup", (event) => setKey(event, false));
+
+function draw() {
+  const width = emulator.width();
+  const height = emulator.height();
+  if (canvas.width !== width || canvas.height !== height) {
+    canvas.width = width;
+    canvas.height = height;
+  }
+  const image = new ImageData(new Uint8ClampedArray(emulator.frame()), width, height);
+  context.putImageData(image, 0, 0);
+}
+
+function tick(time) {
+  if (emulator) {
+    pending += lastTime === null ? FRAME_TIME : time - lastTime;
+    pending = Math.min(pending, FRAME_TIME * MAX_CATCH_UP_FRAMES);
+    while (pending >= FRAME_TIME) {
+      emulator.run_frame();
+      pending -= FRAME_TIME;
+    }
+    if (emulator.display_changed()) {
+      draw();
+    }
+    if (gain) {
+      gain.gain.value = emulator.sound_on() ? 0.1 : 0;
+    }
+  }
+  lastTime = time;
+  requestAnimationFrame(tick);
+}
+
+document.getElementById("rom").addEventListener("change", async (event) => {
+  const file = event.target.files[0];
+  if (!file) {
+    return;
+  }
+  startAudio();
+  const rom = new Uint8Array(await file.arrayBuffer());
+  try {
+    const seed = BigInt(Math.floor(Math.random() * Number.MAX_SAFE_INTEGER));
+    emulator = new Emulator(rom, file.name, seed);
+    draw();
+  } catch (error) {
+    alert(error.message ?? error);
+  }
+});
+
+await init();
+requestAnimationFrame(tick);
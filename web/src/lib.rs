@@ -0,0 +1,119 @@
+use chip8::CHIP8;
+use chip8::keymap;
+use chip8::palette::Palette;
+use chip8::platform::Platform;
+use wasm_bindgen::prelude::*;
+
+// BROWSER FRONTEND
+// The chip as seen from JavaScript. The page (main.js) loads the ROM, runs frames at 60 Hz from
+// requestAnimationFrame, draws the RGBA image on a canvas, feeds in the keys and switches a
+// WebAudio tone on and off with the sound timer. Nothing here touches files or threads.
+
+#[wasm_bindgen]
+pub struct Emulator {
+    chip8: CHIP8,
+    pixels: Vec<u32>, // Colour image of the display, reused for every frame
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    // Create a chip with a ROM loaded
+    // The platform comes from the extension of 'file_name' (.c8x is CHIP-8X) or is detected from
+    // the ROM. 'seed' seeds the random number generator, e.g. from Math.random
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8], file_name: &str, seed: u64) -> Result<Emulator, JsError> {
+        let mut chip8: CHIP8 = CHIP8::new();
+        chip8.seed(seed);
+        let platform: Option<Platform> = Platform::from_file_name(file_name);
+        CHIP8::load_rom_bytes(&mut chip8, rom, platform, None)
+            .map_err(|error| JsError::new(&error.to_string()))?;
+        let pixels: Vec<u32> = vec![0; chip8.platform.width() * chip8.platform.height()];
+        Ok(Emulator { chip8, pixels })
+    }
+
+    pub fn width(&self) -> usize {
+        self.chip8.platform.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.chip8.platform.height()
+    }
+
+    // Run one 60 Hz frame
+    pub fn run_frame(&mut self) {
+        self.chip8.run_frame();
+    }
+
+    // Press or release CHIP-8 key 0x0 to 0xF
+    pub fn set_key(&mut self, key: usize, down: bool) {
+        if let Some(state) = self.chip8.keypad.get_mut(key) {
+            *state = down;
+        }
+    }
+
+    // Press or release key 0x0 to 0xF of the CHIP-8X second keypad
+    pub fn set_key2(&mut self, key: usize, down: bool) {
+        if let Some(state) = self.chip8.keypad2.get_mut(key) {
+            *state = down;
+        }
+    }
+
+    // Whether the display changed since the last call
+    pub fn display_changed(&mut self) -> bool {
+        let changed: bool = self.chip8.display_dirty;
+        self.chip8.display_dirty = false;
+        changed
+    }
+
+    // The display as RGBA bytes, ready for an ImageData
+    pub fn frame(&mut self) -> Vec<u8> {
+        // The platform, and with it the display size, can change while running
+        self.pixels.resize(
+            self.chip8.platform.width() * self.chip8.platform.height(),
+            0,
+        );
+        self.chip8.render(&mut self.pixels);
+        self.pixels
+            .iter()
+            .flat_map(|pixel| {
+                let [_, red, green, blue]: [u8; 4] = pixel.to_be_bytes();
+                [red, green, blue, 0xFF]
+            })
+            .collect()
+    }
+
+    // Whether the beeper should sound
+    pub fn sound_on(&self) -> bool {
+        self.chip8.sound_timer > 0
+    }
+
+    // Show the display in a built-in palette or one given as hex colours, as with --palette
+    pub fn set_palette(&mut self, spec: &str) -> Result<(), JsError> {
+        self.chip8.palette = spec
+            .parse::<Palette>()
+            .map_err(|error| JsError::new(&error))?;
+        self.chip8.display_dirty = true;
+        Ok(())
+    }
+}
+
+// Key 0x0 to 0xF a KeyboardEvent's key stands for, from the layout in chip8::keymap
+// Keys on the numeric keypad ('numpad') belong to the second keypad
+#[wasm_bindgen]
+pub fn keypad_key(key: &str, numpad: bool) -> Option<usize> {
+    let host_key: char = if key == "Enter" {
+        '\n'
+    } else {
+        let mut chars = key.chars();
+        match (chars.next(), chars.next()) {
+            (Some(host_key), None) => host_key.to_ascii_lowercase(),
+            _ => return None,
+        }
+    };
+    let layout: &[char; 16] = if numpad {
+        &keymap::KEYPAD2
+    } else {
+        &keymap::KEYPAD
+    };
+    layout.iter().position(|candidate| *candidate == host_key)
+}